@@ -0,0 +1,116 @@
+//! A decoder for the [toml-test] suite: reads TOML from stdin and writes it to
+//! stdout in toml-test's tagged JSON format, exiting with an error status if
+//! the input is not valid TOML.
+//!
+//! ```console
+//! $ cargo build --example toml-test-decoder
+//! $ toml-test target/debug/examples/toml-test-decoder
+//! ```
+//!
+//! [toml-test]: https://github.com/toml-lang/toml-test
+
+#![deny(warnings)]
+
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::io::{self, Read};
+use std::process;
+
+/// A TOML value, converted into tagged JSON during deserialization: scalars
+/// become `{"type": ..., "value": ...}` objects, while tables and arrays
+/// become JSON objects and arrays.
+struct Tagged(Value);
+
+impl<'de> Deserialize<'de> for Tagged {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(TaggedVisitor)
+    }
+}
+
+struct TaggedVisitor;
+
+fn tag(type_: &str, value: String) -> Tagged {
+    Tagged(json!({ "type": type_, "value": value }))
+}
+
+impl<'de> Visitor<'de> for TaggedVisitor {
+    type Value = Tagged;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Tagged, E> {
+        Ok(tag("bool", v.to_string()))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Tagged, E> {
+        Ok(tag("integer", v.to_string()))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Tagged, E> {
+        Ok(tag("integer", v.to_string()))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Tagged, E> {
+        let value = if v.is_nan() {
+            "nan".to_owned()
+        } else if v.is_infinite() {
+            if v > 0.0 { "inf" } else { "-inf" }.to_owned()
+        } else {
+            v.to_string()
+        };
+        Ok(tag("float", value))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Tagged, E> {
+        Ok(tag("string", v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Tagged, E> {
+        Ok(tag("string", v))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Tagged, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut array = Vec::new();
+        while let Some(Tagged(element)) = seq.next_element()? {
+            array.push(element);
+        }
+        Ok(Tagged(Value::Array(array)))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Tagged, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut table = Map::new();
+        while let Some((key, Tagged(value))) = map.next_entry::<String, Tagged>()? {
+            if table.insert(key.clone(), value).is_some() {
+                return Err(de::Error::custom(format!("duplicate key: `{}`", key)));
+            }
+        }
+        Ok(Tagged(Value::Object(table)))
+    }
+}
+
+fn main() {
+    let mut toml = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut toml) {
+        eprintln!("failed to read stdin: {}", err);
+        process::exit(1);
+    }
+    match basic_toml::from_str::<Tagged>(&toml) {
+        Ok(Tagged(json)) => println!("{}", serde_json::to_string_pretty(&json).unwrap()),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! An encoder for the [toml-test] suite: reads toml-test's tagged JSON format
+//! from stdin and writes the corresponding TOML to stdout, exiting with an
+//! error status if the value cannot be represented.
+//!
+//! ```console
+//! $ cargo build --example toml-test-encoder
+//! $ toml-test -encoder target/debug/examples/toml-test-encoder
+//! ```
+//!
+//! [toml-test]: https://github.com/toml-lang/toml-test
+
+#![deny(warnings)]
+
+use serde::ser::{self, Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::Value;
+use std::io;
+use std::process;
+
+/// Tagged JSON, serialized as the TOML value it describes.
+struct Untagged<'a>(&'a Value);
+
+impl<'a> Serialize for Untagged<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Some((type_, value)) = tagged(self.0) {
+            return match value {
+                Value::String(value) => serialize_scalar(type_, value, serializer),
+                // Older versions of toml-test tag arrays too.
+                Value::Array(_) if type_ == "array" => Untagged(value).serialize(serializer),
+                _ => Err(ser::Error::custom(format!("invalid {}: {}", type_, value))),
+            };
+        }
+        match self.0 {
+            Value::Object(object) => {
+                // The serializer requires a table's plain values to come before
+                // its subtables, whereas JSON objects are sorted by key.
+                let mut map = serializer.serialize_map(Some(object.len()))?;
+                for (key, value) in object.iter().filter(|(_, v)| !is_table(v)) {
+                    map.serialize_entry(key, &Untagged(value))?;
+                }
+                for (key, value) in object.iter().filter(|(_, v)| is_table(v)) {
+                    map.serialize_entry(key, &Untagged(value))?;
+                }
+                map.end()
+            }
+            Value::Array(array) => {
+                let mut seq = serializer.serialize_seq(Some(array.len()))?;
+                for element in array {
+                    seq.serialize_element(&Untagged(element))?;
+                }
+                seq.end()
+            }
+            _ => Err(ser::Error::custom(format!(
+                "expected a tagged value, table or array, found {}",
+                self.0,
+            ))),
+        }
+    }
+}
+
+fn tagged(value: &Value) -> Option<(&str, &Value)> {
+    match value {
+        Value::Object(object) if object.len() == 2 => match object.get("type")? {
+            Value::String(type_) => Some((type_, object.get("value")?)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Whether `value` is written as a table or an array of tables.
+fn is_table(value: &Value) -> bool {
+    match value {
+        Value::Object(_) => tagged(value).is_none(),
+        Value::Array(array) => !array.is_empty() && array.iter().all(is_table),
+        _ => false,
+    }
+}
+
+fn serialize_scalar<S>(type_: &str, value: &str, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let invalid = || ser::Error::custom(format!("invalid {}: {:?}", type_, value));
+    match type_ {
+        "string" => serializer.serialize_str(value),
+        "integer" => serializer.serialize_i64(value.parse().map_err(|_| invalid())?),
+        "float" => {
+            let float = match value.trim_start_matches('+') {
+                "nan" => f64::NAN,
+                "-nan" => -f64::NAN,
+                other => other.parse().map_err(|_| invalid())?,
+            };
+            serializer.serialize_f64(float)
+        }
+        "bool" => serializer.serialize_bool(value.parse().map_err(|_| invalid())?),
+        _ => Err(ser::Error::custom(format!("unsupported type: {}", type_))),
+    }
+}
+
+fn main() {
+    let json: Value = match serde_json::from_reader(io::stdin()) {
+        Ok(json) => json,
+        Err(err) => {
+            eprintln!("invalid JSON: {}", err);
+            process::exit(1);
+        }
+    };
+    match basic_toml::to_string(&Untagged(&json)) {
+        Ok(toml) => print!("{}", toml),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
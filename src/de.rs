@@ -1,27 +1,41 @@
+use crate::options::{DuplicateKey, DuplicateKeys, KeyNormalization, Options, Version};
 use crate::tokens::{Error as TokenError, Span, Token, Tokenizer};
 use serde::de;
 use serde::de::IntoDeserializer;
+use serde::ser;
 use std::borrow::Cow;
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
 use std::error;
 use std::f64;
 use std::fmt::{self, Display};
 use std::iter;
+use std::rc::Rc;
 use std::str;
 use std::vec;
 
-type TablePair<'a> = ((Span, Cow<'a, str>), Value<'a>);
+pub(crate) type TablePair<'a> = ((Span, Cow<'a, str>), Value<'a>);
 
 /// Deserializes a byte slice into a type.
 ///
 /// This function will attempt to interpret `bytes` as UTF-8 data and then
 /// deserialize `T` from the TOML document provided.
 pub fn from_slice<'de, T>(bytes: &'de [u8]) -> Result<T, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    from_slice_with_options(bytes, &Options::default())
+}
+
+pub(crate) fn from_slice_with_options<'de, T>(
+    bytes: &'de [u8],
+    options: &Options,
+) -> Result<T, crate::Error>
 where
     T: de::Deserialize<'de>,
 {
     match str::from_utf8(bytes) {
-        Ok(s) => from_str(s),
+        Ok(s) => from_str_with_options(s, options),
         Err(e) => Err(crate::Error::from(*Error::custom(None, e.to_string()))),
     }
 }
@@ -34,10 +48,221 @@ pub fn from_str<'de, T>(s: &'de str) -> Result<T, crate::Error>
 where
     T: de::Deserialize<'de>,
 {
-    let mut d = Deserializer::new(s);
+    from_str_with_options(s, &Options::default())
+}
+
+pub(crate) fn from_str_with_options<'de, T>(
+    s: &'de str,
+    options: &Options,
+) -> Result<T, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut d = Deserializer::with_options(s, options.clone());
+    T::deserialize(&mut d).map_err(|e| crate::Error::from(*e))
+}
+
+/// Deserializes a string into a type, with overrides taken from environment
+/// variables.
+///
+/// Every environment variable named `{prefix}{separator}{key}...` replaces the
+/// value at the corresponding key path of the document, or adds it if the
+/// document does not have one. For example with prefix `APP` and separator
+/// `__`, the variable `APP__SERVER__PORT=9000` sets `server.port` to the
+/// integer 9000. Key path segments are lowercased.
+///
+/// The content of each variable is parsed as a TOML value, so `[1, 2]` is an
+/// array and strings need to be quoted. Errors caused by an override name the
+/// environment variable instead of a line and column.
+pub fn from_str_with_env<'de, T>(
+    s: &'de str,
+    prefix: &str,
+    separator: &str,
+) -> Result<T, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    from_str_with_env_with_options(s, prefix, separator, &Options::default())
+}
+
+pub(crate) fn from_str_with_env_with_options<'de, T>(
+    s: &'de str,
+    prefix: &str,
+    separator: &str,
+    options: &Options,
+) -> Result<T, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut d = Deserializer::with_options(s, options.clone());
+    d.env_overrides(prefix, separator)
+        .map_err(|e| crate::Error::from(*e))?;
     T::deserialize(&mut d).map_err(|e| crate::Error::from(*e))
 }
 
+/// Deserializes a string into an existing value, updating it in place.
+///
+/// Keys present in the document overwrite the corresponding parts of `place`,
+/// descending into nested tables, while everything that the document does not
+/// mention keeps its current value. Arrays and enums are replaced as a whole,
+/// while maps have the entries of the document added to them.
+///
+/// This works by serializing the current contents of `place` and laying the
+/// document over it, so the type needs to implement `Serialize` as well as
+/// `Deserialize`. The result is deserialized through
+/// [`Deserialize::deserialize_in_place`], so the storage that `place` already
+/// has is reused where the type supports it. For types using
+/// `#[derive(Deserialize)]` that is when the `deserialize_in_place` feature of
+/// `serde_derive` is enabled, which also lets fields marked `#[serde(skip)]`
+/// keep their value rather than being reset to their default. If an error is
+/// returned, `place` may have been partly updated.
+///
+/// [`Deserialize::deserialize_in_place`]: serde::Deserialize::deserialize_in_place
+pub fn from_str_into<'de, T>(s: &'de str, place: &mut T) -> Result<(), crate::Error>
+where
+    T: ser::Serialize + de::Deserialize<'de>,
+{
+    from_str_into_with_options(s, place, &Options::default())
+}
+
+pub(crate) fn from_str_into_with_options<'de, T>(
+    s: &'de str,
+    place: &mut T,
+    options: &Options,
+) -> Result<(), crate::Error>
+where
+    T: ser::Serialize + de::Deserialize<'de>,
+{
+    let base = crate::ser::to_value(place)?;
+    let mut d = Deserializer::with_options(s, options.clone());
+    // The method path would not be general over the lifetime of the visitor.
+    #[allow(clippy::redundant_closure_for_method_calls)]
+    let value = d
+        .deserialize_document(|map| map.capture())
+        .map_err(|e| crate::Error::from(*e))?;
+    let start = value.start;
+    let overlay = OverlayDeserializer {
+        value,
+        base: Some(base),
+        source: Some(d.source()),
+        options: ValueOptions::new(options),
+    };
+    T::deserialize_in_place(overlay, place).map_err(|mut err| {
+        err.fix_offset(|| Some(start));
+        d.fix_location(&mut err);
+        crate::Error::from(*err)
+    })
+}
+
+/// Deserializes the TOML front matter at the start of a file.
+///
+/// Returns `Ok(None)` if the file does not begin with front matter. See
+/// [`front_matter`](crate::front_matter) for the formats that are recognized.
+/// Line and column numbers in errors refer to the whole file rather than to the
+/// front matter alone.
+pub fn from_front_matter<'de, T>(s: &'de str) -> Result<Option<T>, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    from_front_matter_with_options(s, &Options::default())
+}
+
+pub(crate) fn from_front_matter_with_options<'de, T>(
+    s: &'de str,
+    options: &Options,
+) -> Result<Option<T>, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    let range = match crate::front_matter::find(s) {
+        Some(range) => range,
+        None => return Ok(None),
+    };
+    let mut d = Deserializer::with_options(&s[range.clone()], options.clone());
+    d.file = s;
+    d.offset = range.start;
+    match T::deserialize(&mut d) {
+        Ok(value) => Ok(Some(value)),
+        Err(e) => Err(crate::Error::from(*e)),
+    }
+}
+
+/// Deserializes a single TOML value, rather than a whole document.
+///
+/// The input is one expression of the kind that appears on the right-hand side
+/// of `key = ...`, such as `"text"`, `[1, 2, 3]` or `{ x = 1, y = 2 }`,
+/// optionally surrounded by whitespace. Anything after the value is an error.
+pub fn from_value_str<'de, T>(s: &'de str) -> Result<T, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    from_value_str_with_options(s, &Options::default())
+}
+
+pub(crate) fn from_value_str_with_options<'de, T>(
+    s: &'de str,
+    options: &Options,
+) -> Result<T, crate::Error>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut d = Deserializer::with_options(s, options.clone());
+    let value = d.standalone_value().map_err(|e| crate::Error::from(*e))?;
+    let start = value.start;
+    let source = Some(d.source());
+    let de = ValueDeserializer::new(value)
+        .with_source(source)
+        .with_options(ValueOptions::new(options));
+    T::deserialize(de).map_err(|mut err| {
+        err.fix_offset(|| Some(start));
+        d.fix_location(&mut err);
+        crate::Error::from(*err)
+    })
+}
+
+// Deserializes a value captured by `RawValue`. The `env` function gives the
+// environment variable that an offset was taken from, if any, and `linecol`
+// the location of an offset in the original document, if it is known.
+pub(crate) fn from_raw_value<T>(
+    value: Value<'static>,
+    env: &dyn Fn(usize) -> Option<String>,
+    linecol: Option<&dyn Fn(usize) -> (usize, usize)>,
+    options: &Options,
+) -> Result<T, crate::Error>
+where
+    T: de::DeserializeOwned,
+{
+    let start = value.start;
+    let de = ValueDeserializer::new(value).with_options(ValueOptions::new(options));
+    T::deserialize(de).map_err(|mut err| {
+        err.fix_offset(|| Some(start));
+        if let Some(var) = err.at.and_then(env) {
+            err.env = Some(var);
+        } else if let Some(linecol) = linecol {
+            err.fix_linecol(linecol);
+        }
+        crate::Error::from(*err)
+    })
+}
+
+// Parses `s` as a single integer or float, for `Number`.
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) fn parse_number(s: &str) -> Result<Value<'_>, crate::Error> {
+    let value = Deserializer::new(s)
+        .standalone_value()
+        .map_err(|e| crate::Error::from(*e))?;
+    match value.e {
+        E::Number(..) => Ok(value),
+        ref e => {
+            let kind = ErrorKind::Wanted {
+                expected: "a number",
+                found: e.type_name(),
+            };
+            Err(crate::Error::from(*Error::from_kind(None, kind)))
+        }
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct Error {
     kind: ErrorKind,
@@ -46,6 +271,7 @@ pub(crate) struct Error {
     at: Option<usize>,
     message: String,
     key: Vec<String>,
+    env: Option<String>,
 }
 
 /// Errors that can occur when deserializing a type.
@@ -85,6 +311,35 @@ enum ErrorKind {
     /// A number failed to parse.
     NumberInvalid,
 
+    /// An integer is larger than `i64::MAX`.
+    IntegerTooLarge,
+
+    /// An integer is smaller than `i64::MIN`.
+    IntegerTooSmall,
+
+    /// A float is too large in magnitude to be represented as a finite `f64`.
+    FloatOutOfRange,
+
+    /// A decimal integer, or the integer part of a float, starts with a zero
+    /// followed by more digits.
+    LeadingZero,
+
+    /// An underscore in a number is not between two digits.
+    MisplacedUnderscore,
+
+    /// A float has a decimal point that is not followed by digits.
+    MissingFraction,
+
+    /// A float has an exponent marker that is not followed by digits.
+    MissingExponent,
+
+    /// A hexadecimal, octal or binary integer has a sign.
+    SignedPrefixedInteger,
+
+    /// A hexadecimal, octal or binary integer has a sign after its prefix, as
+    /// in `0x-1`.
+    SignAfterPrefix,
+
     /// Wanted one sort of token, but found another.
     Wanted {
         /// Expected token type.
@@ -99,6 +354,16 @@ enum ErrorKind {
     /// Duplicate key in table.
     DuplicateKey(String),
 
+    /// Two different keys in a table are the same after normalization.
+    KeyCollision {
+        /// The key that came first.
+        first: String,
+        /// The key that came second.
+        second: String,
+        /// What both keys normalize to.
+        normalized: String,
+    },
+
     /// A previously defined table was redefined as an array.
     RedefineAsArray,
 
@@ -144,7 +409,28 @@ enum ErrorKind {
 
 struct Deserializer<'a> {
     input: &'a str,
+    // The file that `input` was cut out of, such as a Markdown file with TOML
+    // front matter, and the position of `input` in it. Error locations are
+    // reported relative to the whole file.
+    file: &'a str,
+    offset: usize,
     tokens: Tokenizer<'a>,
+    overrides: Vec<Override>,
+    // The names of the environment variables in `overrides`.
+    env: Rc<[String]>,
+    options: Options,
+    // The keys leading to the table whose entries are being parsed, for
+    // reporting duplicate keys.
+    path: Vec<Cow<'a, str>>,
+}
+
+// A value taken from an environment variable, to be stored at `path` in place
+// of whatever the document has there. Its spans point past the end of the
+// input, at `input.len() + 1 + i` for the i'th override, so that errors can be
+// attributed to the variable.
+struct Override {
+    path: Vec<Cow<'static, str>>,
+    value: Value<'static>,
 }
 
 impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
@@ -154,44 +440,73 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: de::Visitor<'de>,
     {
-        let mut tables = self.tables()?;
-        let table_indices = build_table_indices(&tables);
-        let table_pindices = build_table_pindices(&tables);
+        self.deserialize_document(|map| visitor.visit_map(map))
+    }
 
-        let res = visitor.visit_map(MapVisitor {
-            values: Vec::new().into_iter().peekable(),
-            next_value: None,
-            depth: 0,
-            cur: 0,
-            cur_parent: 0,
-            max: tables.len(),
-            table_indices: &table_indices,
-            table_pindices: &table_pindices,
-            tables: &mut tables,
-            array: false,
-            de: self,
-            keys: HashSet::new(),
-        });
-        res.map_err(|mut err| {
-            // Errors originating from this library (toml), have an offset
-            // attached to them already. Other errors, like those originating
-            // from serde (like "missing field") or from a custom deserializer,
-            // do not have offsets on them. Here, we do a best guess at their
-            // location, by attributing them to the "current table" (the last
-            // item in `tables`).
-            err.fix_offset(|| tables.last().map(|table| table.at));
-            err.fix_linecol(|at| self.to_linecol(at));
-            err
-        })
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_document(|map| visitor.visit_enum(map))
+    }
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(UnitVisitor(visitor))
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(UnitVisitor(visitor))
     }
 
     serde::forward_to_deserialize_any! {
         bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string seq
-        bytes byte_buf map unit newtype_struct
-        ignored_any unit_struct tuple_struct tuple option identifier struct enum
+        bytes byte_buf map newtype_struct
+        ignored_any tuple_struct tuple option identifier struct
     }
 }
 
+// Whether the key path of `header` starts with `path`.
+fn starts_with(header: &[(Span, Cow<str>)], path: &[Cow<str>]) -> bool {
+    header.len() >= path.len() && header.iter().zip(path).all(|(part, key)| part.1 == *key)
+}
+
+// Marks the tables which belong to an element of an array of tables on `path`
+// other than the last one, such as the first `[[a]]` and `[a.b]` in
+//
+//      [[a]]
+//      [a.b]
+//      [[a]]
+//
+// for the path `a.b.c`.
+fn stale_tables(tables: &[Table], path: &[Cow<str>]) -> Vec<bool> {
+    (0..tables.len())
+        .map(|i| {
+            tables[i + 1..].iter().any(|later| {
+                let array = &later.header;
+                later.array
+                    && array.len() < path.len()
+                    && starts_with(array, &path[..array.len()])
+                    && tables[i].header.len() >= array.len()
+                    && tables[i].header.iter().zip(array).all(|(a, b)| a.1 == b.1)
+            })
+        })
+        .collect()
+}
+
 // Builds a datastructure that allows for efficient sublinear lookups. The
 // returned HashMap contains a mapping from table header (like [a.b.c]) to list
 // of tables with that precise name. The tables are being identified by their
@@ -243,6 +558,26 @@ fn headers_equal(hdr_a: &[(Span, Cow<str>)], hdr_b: &[(Span, Cow<str>)]) -> bool
     hdr_a.iter().zip(hdr_b.iter()).all(|(h1, h2)| h1.1 == h2.1)
 }
 
+// Finds the table among `tables` with a dotted table that `header` adds a
+// sub-table to, returning its index and the position of the dotted table's key
+// in `header`.
+fn dotted_owner(tables: &[Table], header: &[(Span, Cow<str>)]) -> Option<(usize, usize)> {
+    (0..header.len().saturating_sub(1)).rev().find_map(|k| {
+        let j = tables
+            .iter()
+            .rposition(|table| headers_equal(&table.header, &header[..k]))?;
+        let values = tables[j].values.as_ref()?;
+        let dotted = values
+            .iter()
+            .any(|(key, value)| key.1 == header[k].1 && matches!(value.e, E::DottedTable(_)));
+        if dotted {
+            Some((j, k))
+        } else {
+            None
+        }
+    })
+}
+
 struct Table<'a> {
     at: usize,
     header: Vec<(Span, Cow<'a, str>)>,
@@ -262,7 +597,10 @@ struct MapVisitor<'de, 'b> {
     tables: &'b mut [Table<'de>],
     array: bool,
     de: &'b mut Deserializer<'de>,
-    keys: HashSet<Cow<'de, str>>,
+    keys: HashMap<Cow<'de, str>, Cow<'de, str>>,
+    // Where the last key came from: the key itself for a key/value pair, the
+    // header for a table.
+    key_at: Option<usize>,
 }
 
 impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
@@ -279,13 +617,9 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
         loop {
             assert!(self.next_value.is_none());
             if let Some(((span, key), value)) = self.values.next() {
-                if !self.keys.insert(key.clone()) {
-                    return Err(Error::from_kind(
-                        Some(span.start),
-                        ErrorKind::DuplicateKey(key.into_owned()),
-                    ));
-                }
-                let ret = seed.deserialize(StrDeserializer::new(key.clone()))?;
+                let name = insert_key(&mut self.keys, &key, span, self.de.options.keys)?;
+                self.key_at = Some(span.start);
+                let ret = seed.deserialize(StrDeserializer::new(name))?;
                 self.next_value = Some(((span, key), value));
                 return Ok(Some(ret));
             }
@@ -352,13 +686,9 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
             // decoding.
             if self.depth != table.header.len() {
                 let (span, key) = &table.header[self.depth];
-                if !self.keys.insert(key.clone()) {
-                    return Err(Error::from_kind(
-                        Some(span.start),
-                        ErrorKind::DuplicateKey(key.clone().into_owned()),
-                    ));
-                }
-                let key = seed.deserialize(StrDeserializer::new(key.clone()))?;
+                let name = insert_key(&mut self.keys, key, *span, self.de.options.keys)?;
+                self.key_at = Some(table.at);
+                let key = seed.deserialize(StrDeserializer::new(name))?;
                 return Ok(Some(key));
             }
 
@@ -385,7 +715,13 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
         V: de::DeserializeSeed<'de>,
     {
         if let Some((k, v)) = self.next_value.take() {
-            match seed.deserialize(ValueDeserializer::new(v)) {
+            let source = Some(self.de.source());
+            let options = ValueOptions::new(&self.de.options);
+            match seed.deserialize(
+                ValueDeserializer::new(v)
+                    .with_source(source)
+                    .with_options(options),
+            ) {
                 Ok(v) => return Ok(v),
                 Err(mut e) => {
                     e.add_key_context(&k.1);
@@ -409,7 +745,8 @@ impl<'de, 'b> de::MapAccess<'de> for MapVisitor<'de, 'b> {
             table_pindices: self.table_pindices,
             tables: &mut *self.tables,
             de: &mut *self.de,
-            keys: HashSet::new(),
+            keys: HashMap::new(),
+            key_at: None,
         });
         res.map_err(|mut e| {
             e.add_key_context(&self.tables[self.cur - 1].header[self.depth].1);
@@ -472,13 +809,61 @@ impl<'de, 'b> de::SeqAccess<'de> for MapVisitor<'de, 'b> {
             table_pindices: self.table_pindices,
             tables: self.tables,
             de: self.de,
-            keys: HashSet::new(),
+            keys: HashMap::new(),
+            key_at: None,
         })?;
         self.cur_parent = next;
         Ok(Some(ret))
     }
 }
 
+impl<'de, 'b> MapVisitor<'de, 'b> {
+    // Collects the table, or array of tables, into a single value for
+    // `RawValue`. Keys are attributed to the position of their value.
+    fn capture(mut self) -> Result<Value<'static>, Box<Error>> {
+        let at = self.tables[self.cur_parent].at;
+        let e = if self.array {
+            let mut values = Vec::new();
+            while let Some(value) = de::SeqAccess::next_element_seed(&mut self, ValueSeed)? {
+                values.push(value);
+            }
+            E::Array(values)
+        } else {
+            let mut values = Vec::new();
+            while let Some(key) = de::MapAccess::next_key::<String>(&mut self)? {
+                let value = de::MapAccess::next_value_seed(&mut self, ValueSeed)?;
+                let span = Span {
+                    start: value.start,
+                    end: value.start,
+                };
+                values.push(((span, Cow::Owned(key)), value));
+            }
+            E::InlineTable(values)
+        };
+        Ok(Value {
+            e,
+            start: at,
+            end: at,
+        })
+    }
+}
+
+// Deserializes the value that a TOML deserializer holds as is, with its
+// positions in the input.
+struct ValueSeed;
+
+impl<'de> de::DeserializeSeed<'de> for ValueSeed {
+    type Value = Value<'static>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        let raw = crate::raw::deserialize(deserializer, crate::raw::VALUE_TOKEN)?;
+        Ok(raw.into_value())
+    }
+}
+
 impl<'de, 'b> de::Deserializer<'de> for MapVisitor<'de, 'b> {
     type Error = Box<Error>;
 
@@ -504,19 +889,198 @@ impl<'de, 'b> de::Deserializer<'de> for MapVisitor<'de, 'b> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Box<Error>>
     where
         V: de::Visitor<'de>,
     {
+        if name == crate::raw::TOKEN || name == crate::raw::VALUE_TOKEN {
+            let source = if name == crate::raw::TOKEN {
+                Some(self.de.source())
+            } else {
+                None
+            };
+            let value = self.capture()?;
+            return visitor.visit_seq(crate::raw::RawAccess::new(value, source));
+        }
         visitor.visit_newtype_struct(self)
     }
 
+    // A table with a single key, like `[storage.s3]` under `[storage]`, holds
+    // the variant named by that key.
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        if self.array {
+            self.deserialize_any(visitor)
+        } else {
+            visitor.visit_enum(self)
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(UnitVisitor(visitor))
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(UnitVisitor(visitor))
+    }
+
     serde::forward_to_deserialize_any! {
         bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string seq
-        bytes byte_buf map unit identifier
-        ignored_any unit_struct tuple_struct tuple struct enum
+        bytes byte_buf map identifier
+        ignored_any tuple_struct tuple struct
+    }
+}
+
+impl<'de, 'b> de::EnumAccess<'de> for MapVisitor<'de, 'b> {
+    type Error = Box<Error>;
+    type Variant = Self;
+
+    fn variant_seed<V>(mut self, seed: V) -> Result<(V::Value, Self::Variant), Box<Error>>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        if let Some(variant) = de::MapAccess::next_key_seed(&mut self, seed)? {
+            Ok((variant, self))
+        } else {
+            let at = self.tables.get(self.cur_parent).map(|t| t.at);
+            Err(enum_error(at, "zero elements"))
+        }
+    }
+}
+
+impl<'de, 'b> de::VariantAccess<'de> for MapVisitor<'de, 'b> {
+    type Error = Box<Error>;
+
+    fn unit_variant(mut self) -> Result<(), Box<Error>> {
+        de::MapAccess::next_value::<()>(&mut self)?;
+        self.end_enum()
+    }
+
+    fn newtype_variant_seed<T>(mut self, seed: T) -> Result<T::Value, Box<Error>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        let value = de::MapAccess::next_value_seed(&mut self, seed)?;
+        self.end_enum()?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(mut self, len: usize, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        let seed = VariantSeed::Tuple(len, visitor);
+        let value = de::MapAccess::next_value_seed(&mut self, seed)?;
+        self.end_enum()?;
+        Ok(value)
+    }
+
+    fn struct_variant<V>(
+        mut self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        let seed = VariantSeed::Struct(fields, visitor);
+        let value = de::MapAccess::next_value_seed(&mut self, seed)?;
+        self.end_enum()?;
+        Ok(value)
+    }
+}
+
+impl<'de, 'b> MapVisitor<'de, 'b> {
+    // Checks that the variant was the only key in the table.
+    fn end_enum(mut self) -> Result<(), Box<Error>> {
+        match de::MapAccess::next_key::<de::IgnoredAny>(&mut self)? {
+            Some(_) => Err(enum_error(self.key_at, "more than 1 element")),
+            None => Ok(()),
+        }
+    }
+}
+
+fn enum_error(at: Option<usize>, found: &'static str) -> Box<Error> {
+    let kind = ErrorKind::Wanted {
+        expected: "exactly 1 element",
+        found,
+    };
+    Error::from_kind(at, kind)
+}
+
+// Deserializes the content of a tuple or struct variant.
+enum VariantSeed<V> {
+    Tuple(usize, V),
+    Struct(&'static [&'static str], V),
+}
+
+impl<'de, V> de::DeserializeSeed<'de> for VariantSeed<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<V::Value, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        match self {
+            VariantSeed::Tuple(len, visitor) => deserializer.deserialize_tuple(len, visitor),
+            VariantSeed::Struct(fields, visitor) => {
+                deserializer.deserialize_struct("", fields, visitor)
+            }
+        }
+    }
+}
+
+// Accepts an empty table, which is how unit and unit structs are written, in
+// addition to whatever `V` accepts.
+struct UnitVisitor<V>(V);
+
+impl<'de, V> de::Visitor<'de> for UnitVisitor<V>
+where
+    V: de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        self.0.expecting(formatter)
+    }
+
+    fn visit_unit<E>(self) -> Result<V::Value, E>
+    where
+        E: de::Error,
+    {
+        self.0.visit_unit()
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<V::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        if map.next_key::<de::IgnoredAny>()?.is_some() {
+            return Err(de::Error::invalid_type(de::Unexpected::Map, &self));
+        }
+        self.0.visit_unit()
     }
 }
 
@@ -558,9 +1122,41 @@ impl<'de> de::Deserializer<'de> for StrDeserializer<'de> {
     }
 }
 
-struct ValueDeserializer<'a> {
+macro_rules! deserialize_coerced_integer {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.coerce_str(coerce_integer).deserialize_any(visitor)
+            }
+        )*
+    };
+}
+
+// The settings from `Options` that apply to values deserialized outside of the
+// document's tables, such as inline tables and arrays.
+#[derive(Copy, Clone, Default)]
+struct ValueOptions {
+    coerce: bool,
+    keys: Option<KeyNormalization>,
+}
+
+impl ValueOptions {
+    fn new(options: &Options) -> Self {
+        ValueOptions {
+            coerce: options.coerce,
+            keys: options.keys,
+        }
+    }
+}
+
+pub(crate) struct ValueDeserializer<'a> {
     value: Value<'a>,
     validate_struct_keys: bool,
+    source: Option<Source<'a>>,
+    options: ValueOptions,
 }
 
 impl<'a> ValueDeserializer<'a> {
@@ -568,7 +1164,32 @@ impl<'a> ValueDeserializer<'a> {
         ValueDeserializer {
             value,
             validate_struct_keys: false,
+            source: None,
+            options: ValueOptions::default(),
+        }
+    }
+
+    fn with_source(mut self, source: Option<Source<'a>>) -> Self {
+        self.source = source;
+        self
+    }
+
+    fn with_options(mut self, options: ValueOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // With coercion enabled, replaces a string with the value that `parse`
+    // makes of it, if any.
+    fn coerce_str(mut self, parse: fn(&str) -> Option<E<'static>>) -> Self {
+        if self.options.coerce {
+            if let E::String(s) = &self.value.e {
+                if let Some(e) = parse(s.trim()) {
+                    self.value.e = e;
+                }
+            }
         }
+        self
     }
 
     fn with_struct_key_validation(mut self) -> Self {
@@ -585,6 +1206,8 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
         V: de::Visitor<'de>,
     {
         let start = self.value.start;
+        let source = self.source;
+        let options = self.options;
         let res = match self.value.e {
             E::Integer(i) => visitor.visit_i64(i),
             E::Boolean(b) => visitor.visit_bool(b),
@@ -592,7 +1215,12 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
             E::String(Cow::Borrowed(s)) => visitor.visit_borrowed_str(s),
             E::String(Cow::Owned(s)) => visitor.visit_string(s),
             E::Array(values) => {
-                let mut s = de::value::SeqDeserializer::new(values.into_iter());
+                let values = values.into_iter().map(|value| {
+                    ValueDeserializer::new(value)
+                        .with_source(source.clone())
+                        .with_options(options)
+                });
+                let mut s = de::value::SeqDeserializer::new(values);
                 let ret = visitor.visit_seq(&mut s)?;
                 s.end()?;
                 Ok(ret)
@@ -601,9 +1229,21 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
                 visitor.visit_map(InlineTableDeserializer {
                     values: values.into_iter(),
                     next_value: None,
-                    keys: HashSet::new(),
+                    keys: HashMap::new(),
+                    source,
+                    options,
                 })
             }
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(_, Some(e)) => {
+                let end = self.value.end;
+                ValueDeserializer::new(Value { e: *e, start, end })
+                    .with_source(source)
+                    .with_options(options)
+                    .deserialize_any(visitor)
+            }
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(lexeme, None) => Err(Error::from_kind(Some(start), out_of_range(&lexeme))),
         };
         res.map_err(|mut err| {
             // Attribute the error to whatever value returned the error.
@@ -628,7 +1268,8 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
                         .iter()
                         .filter_map(|key_value| {
                             let (ref key, ref _val) = *key_value;
-                            if fields.contains(&&*(key.1)) {
+                            let name = normalize_key(key.1.clone(), self.options.keys);
+                            if fields.contains(&&*name) {
                                 None
                             } else {
                                 Some(key.clone())
@@ -675,11 +1316,36 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
         V: de::Visitor<'de>,
     {
         match self.value.e {
-            E::String(val) => visitor.visit_enum(val.into_deserializer()),
+            E::String(val) => {
+                visitor.visit_enum(normalize_key(val, self.options.keys).into_deserializer())
+            }
+            E::InlineTable(values) | E::DottedTable(values) => {
+                if values.len() == 1 {
+                    visitor.visit_enum(InlineTableDeserializer {
+                        values: values.into_iter(),
+                        next_value: None,
+                        keys: HashMap::new(),
+                        source: self.source,
+                        options: self.options,
+                    })
+                } else {
+                    Err(Error::from_kind(
+                        Some(self.value.start),
+                        ErrorKind::Wanted {
+                            expected: "exactly 1 element",
+                            found: if values.is_empty() {
+                                "zero elements"
+                            } else {
+                                "more than 1 element"
+                            },
+                        },
+                    ))
+                }
+            }
             e => Err(Error::from_kind(
                 Some(self.value.start),
                 ErrorKind::Wanted {
-                    expected: "string",
+                    expected: "string or table",
                     found: e.type_name(),
                 },
             )),
@@ -688,19 +1354,189 @@ impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Box<Error>>
     where
         V: de::Visitor<'de>,
     {
+        if name == crate::raw::TOKEN || name == crate::raw::VALUE_TOKEN {
+            let source = if name == crate::raw::TOKEN {
+                self.source
+            } else {
+                None
+            };
+            let value = self.value.into_static();
+            return visitor.visit_seq(crate::raw::RawAccess::new(value, source));
+        }
+        #[cfg(feature = "arbitrary_precision")]
+        if name == crate::number::TOKEN {
+            return match self.value.e {
+                E::Number(Cow::Borrowed(s), _) => visitor.visit_borrowed_str(s),
+                E::Number(Cow::Owned(s), _) => visitor.visit_string(s),
+                e => Err(Error::from_kind(
+                    Some(self.value.start),
+                    ErrorKind::Wanted {
+                        expected: "a number",
+                        found: e.type_name(),
+                    },
+                )),
+            };
+        }
         visitor.visit_newtype_struct(self)
     }
 
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(UnitVisitor(visitor))
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(UnitVisitor(visitor))
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.coerce_str(coerce_bool).deserialize_any(visitor)
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.coerce_str(coerce_float).deserialize_any(visitor)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.coerce_str(coerce_float).deserialize_any(visitor)
+    }
+
+    deserialize_coerced_integer! {
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+    }
+
+    // With coercion enabled, anything other than an array is deserialized as
+    // an array of one element.
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        match self.value.e {
+            E::Array(_) => self.deserialize_any(visitor),
+            _ if self.options.coerce => {
+                let mut s = de::value::SeqDeserializer::new(iter::once(self));
+                let ret = visitor.visit_seq(&mut s)?;
+                s.end()?;
+                Ok(ret)
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
     serde::forward_to_deserialize_any! {
-        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string seq
-        bytes byte_buf map unit identifier
-        ignored_any unit_struct tuple_struct tuple
+        char str string bytes byte_buf map identifier
+        ignored_any tuple_struct tuple
+    }
+}
+
+// Returns the name that `key` is given to the visitor under.
+fn normalize_key(key: Cow<str>, rule: Option<KeyNormalization>) -> Cow<str> {
+    match rule.and_then(|rule| rule.apply(&key)) {
+        Some(name) => Cow::Owned(name),
+        None => key,
+    }
+}
+
+// Records `key` among the keys seen in a table, by its normalized name, and
+// returns that name. A different key with the same name is a collision.
+fn insert_key<'de>(
+    keys: &mut HashMap<Cow<'de, str>, Cow<'de, str>>,
+    key: &Cow<'de, str>,
+    span: Span,
+    rule: Option<KeyNormalization>,
+) -> Result<Cow<'de, str>, Box<Error>> {
+    let name = normalize_key(key.clone(), rule);
+    match keys.entry(name.clone()) {
+        Entry::Vacant(entry) => {
+            entry.insert(key.clone());
+            Ok(name)
+        }
+        Entry::Occupied(entry) => {
+            let kind = if entry.get() == key {
+                ErrorKind::DuplicateKey(key.to_string())
+            } else {
+                ErrorKind::KeyCollision {
+                    first: entry.get().to_string(),
+                    second: key.to_string(),
+                    normalized: name.into_owned(),
+                }
+            };
+            Err(Error::from_kind(Some(span.start), kind))
+        }
+    }
+}
+
+fn is_prefixed_integer(s: &str) -> bool {
+    s.starts_with("0x") || s.starts_with("0o") || s.starts_with("0b")
+}
+
+// The error for a number whose text was kept by `number` because it is out of
+// range.
+#[cfg(feature = "arbitrary_precision")]
+fn out_of_range(lexeme: &str) -> ErrorKind {
+    let digits = lexeme.trim_start_matches(['+', '-']);
+    if !is_prefixed_integer(digits) && digits.contains(['.', 'e', 'E']) {
+        ErrorKind::FloatOutOfRange
+    } else if lexeme.starts_with('-') {
+        ErrorKind::IntegerTooSmall
+    } else {
+        ErrorKind::IntegerTooLarge
+    }
+}
+
+fn coerce_bool(s: &str) -> Option<E<'static>> {
+    match s {
+        "true" => Some(E::Boolean(true)),
+        "false" => Some(E::Boolean(false)),
+        _ => None,
+    }
+}
+
+fn coerce_integer(s: &str) -> Option<E<'static>> {
+    coerce_number(s).filter(|e| matches!(e, E::Integer(_)))
+}
+
+fn coerce_float(s: &str) -> Option<E<'static>> {
+    coerce_number(s)
+}
+
+// Parses a TOML integer or float literal, such as `1_000`, `0x10` or `inf`.
+fn coerce_number(s: &str) -> Option<E<'static>> {
+    let value = Deserializer::new(s).standalone_value().ok()?;
+    #[cfg(feature = "arbitrary_precision")]
+    let value = match value.e {
+        E::Number(_, Some(e)) => Value { e: *e, ..value },
+        _ => value,
+    };
+    match value.e {
+        E::Integer(i) => Some(E::Integer(i)),
+        E::Float(f) => Some(E::Float(f)),
+        _ => None,
     }
 }
 
@@ -718,20 +1554,246 @@ impl<'de> de::IntoDeserializer<'de, Box<Error>> for &mut Deserializer<'de> {
     fn into_deserializer(self) -> Self::Deserializer {
         self
     }
-}
-
-impl<'de> de::IntoDeserializer<'de, Box<Error>> for Value<'de> {
-    type Deserializer = ValueDeserializer<'de>;
+}
+
+impl<'de> de::IntoDeserializer<'de, Box<Error>> for Value<'de> {
+    type Deserializer = ValueDeserializer<'de>;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        ValueDeserializer::new(self)
+    }
+}
+
+impl<'de> de::IntoDeserializer<'de, Box<Error>> for ValueDeserializer<'de> {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+// Deserializes a value of the document for `from_str_into`, taking whatever
+// tables of the document leave out from `base`, the serialized current value
+// of the place. Anything other than a table, such as an array or an enum, is
+// taken from the document as a whole.
+struct OverlayDeserializer<'de> {
+    value: Value<'de>,
+    base: Option<Value<'de>>,
+    source: Option<Source<'de>>,
+    options: ValueOptions,
+}
+
+impl<'de> OverlayDeserializer<'de> {
+    fn value(self) -> ValueDeserializer<'de> {
+        ValueDeserializer::new(self.value)
+            .with_source(self.source)
+            .with_options(self.options)
+    }
+}
+
+macro_rules! forward_to_value {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+            where
+                V: de::Visitor<'de>,
+            {
+                self.value().$method(visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for OverlayDeserializer<'de> {
+    type Error = Box<Error>;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        let values = match self.value.e {
+            E::InlineTable(values) | E::DottedTable(values) => values,
+            e => {
+                let value = Value { e, ..self.value };
+                return OverlayDeserializer { value, ..self }
+                    .value()
+                    .deserialize_any(visitor);
+            }
+        };
+        let base = match self.base {
+            Some(Value {
+                e: E::InlineTable(base) | E::DottedTable(base),
+                ..
+            }) => base,
+            _ => Vec::new(),
+        };
+        let start = self.value.start;
+        let res = visitor.visit_map(OverlayTable {
+            values: values.into_iter(),
+            base,
+            next_value: None,
+            keys: HashMap::new(),
+            source: self.source,
+            options: self.options,
+        });
+        res.map_err(|mut err| {
+            err.fix_offset(|| Some(start));
+            err
+        })
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        if name.starts_with("$__basic_toml_private_") {
+            self.value().deserialize_newtype_struct(name, visitor)
+        } else {
+            visitor.visit_newtype_struct(self)
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value().deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value().deserialize_unit_struct(name, visitor)
+    }
+
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value().deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Box<Error>>
+    where
+        V: de::Visitor<'de>,
+    {
+        self.value().deserialize_tuple_struct(name, len, visitor)
+    }
+
+    forward_to_value! {
+        deserialize_bool deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_f32 deserialize_f64 deserialize_char deserialize_str deserialize_string
+        deserialize_bytes deserialize_byte_buf deserialize_unit deserialize_seq
+        deserialize_identifier deserialize_ignored_any
+    }
+}
+
+// The entries of a table of the document for `from_str_into`, followed by the
+// entries of the current value that the document does not have.
+struct OverlayTable<'de> {
+    values: vec::IntoIter<TablePair<'de>>,
+    base: Vec<TablePair<'de>>,
+    next_value: Option<(Cow<'de, str>, Value<'de>, Option<Value<'de>>)>,
+    keys: HashMap<Cow<'de, str>, Cow<'de, str>>,
+    source: Option<Source<'de>>,
+    options: ValueOptions,
+}
+
+impl<'de> de::MapAccess<'de> for OverlayTable<'de> {
+    type Error = Box<Error>;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Box<Error>>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        let name = if let Some(((span, key), value)) = self.values.next() {
+            let name = insert_key(&mut self.keys, &key, span, self.options.keys)?;
+            let base = match self.base.iter().position(|(k, _)| k.1 == name) {
+                Some(i) => Some(self.base.remove(i).1),
+                None => None,
+            };
+            self.next_value = Some((name.clone(), value, base));
+            name
+        } else if !self.base.is_empty() {
+            let ((_, name), value) = self.base.remove(0);
+            self.next_value = Some((name.clone(), value, None));
+            name
+        } else {
+            return Ok(None);
+        };
+        seed.deserialize(StrDeserializer::new(name)).map(Some)
+    }
 
-    fn into_deserializer(self) -> Self::Deserializer {
-        ValueDeserializer::new(self)
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Box<Error>>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        let (key, value, base) = self.next_value.take().expect("Unable to read table values");
+        let res = seed.deserialize(OverlayDeserializer {
+            value,
+            base,
+            source: self.source.clone(),
+            options: self.options,
+        });
+        res.map_err(|mut e| {
+            e.add_key_context(&key);
+            e
+        })
     }
 }
 
 struct InlineTableDeserializer<'de> {
     values: vec::IntoIter<TablePair<'de>>,
     next_value: Option<Value<'de>>,
-    keys: HashSet<Cow<'de, str>>,
+    keys: HashMap<Cow<'de, str>, Cow<'de, str>>,
+    source: Option<Source<'de>>,
+    options: ValueOptions,
 }
 
 impl<'de> de::MapAccess<'de> for InlineTableDeserializer<'de> {
@@ -746,13 +1808,8 @@ impl<'de> de::MapAccess<'de> for InlineTableDeserializer<'de> {
             None => return Ok(None),
         };
         self.next_value = Some(value);
-        if !self.keys.insert(key.clone()) {
-            return Err(Error::from_kind(
-                Some(span.start),
-                ErrorKind::DuplicateKey(key.into_owned()),
-            ));
-        }
-        seed.deserialize(StrDeserializer::new(key)).map(Some)
+        let name = insert_key(&mut self.keys, &key, span, self.options.keys)?;
+        seed.deserialize(StrDeserializer::new(name)).map(Some)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Box<Error>>
@@ -760,7 +1817,11 @@ impl<'de> de::MapAccess<'de> for InlineTableDeserializer<'de> {
         V: de::DeserializeSeed<'de>,
     {
         let value = self.next_value.take().expect("Unable to read table values");
-        seed.deserialize(ValueDeserializer::new(value))
+        seed.deserialize(
+            ValueDeserializer::new(value)
+                .with_source(self.source.clone())
+                .with_options(self.options),
+        )
     }
 }
 
@@ -785,14 +1846,27 @@ impl<'de> de::EnumAccess<'de> for InlineTableDeserializer<'de> {
             }
         };
 
-        seed.deserialize(StrDeserializer::new(key.1))
-            .map(|val| (val, TableEnumDeserializer { value }))
+        let name = normalize_key(key.1, self.options.keys);
+        seed.deserialize(StrDeserializer::new(name)).map(|val| {
+            let source = self.source;
+            let options = self.options;
+            (
+                val,
+                TableEnumDeserializer {
+                    value,
+                    source,
+                    options,
+                },
+            )
+        })
     }
 }
 
 /// Deserializes table values into enum variants.
 struct TableEnumDeserializer<'a> {
     value: Value<'a>,
+    source: Option<Source<'a>>,
+    options: ValueOptions,
 }
 
 impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
@@ -824,7 +1898,11 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        seed.deserialize(ValueDeserializer::new(self.value))
+        seed.deserialize(
+            ValueDeserializer::new(self.value)
+                .with_source(self.source)
+                .with_options(self.options),
+        )
     }
 
     fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
@@ -854,7 +1932,28 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
                             e: E::Array(tuple_values),
                             start: self.value.start,
                             end: self.value.end,
-                        }),
+                        })
+                        .with_source(self.source)
+                        .with_options(self.options),
+                        visitor,
+                    )
+                } else {
+                    Err(Error::from_kind(
+                        Some(self.value.start),
+                        ErrorKind::ExpectedTuple(len),
+                    ))
+                }
+            }
+            E::Array(values) => {
+                if values.len() == len {
+                    de::Deserializer::deserialize_seq(
+                        ValueDeserializer::new(Value {
+                            e: E::Array(values),
+                            start: self.value.start,
+                            end: self.value.end,
+                        })
+                        .with_source(self.source)
+                        .with_options(self.options),
                         visitor,
                     )
                 } else {
@@ -867,7 +1966,7 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
             e => Err(Error::from_kind(
                 Some(self.value.start),
                 ErrorKind::Wanted {
-                    expected: "table",
+                    expected: "array or table",
                     found: e.type_name(),
                 },
             )),
@@ -883,7 +1982,10 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
         V: de::Visitor<'de>,
     {
         de::Deserializer::deserialize_struct(
-            ValueDeserializer::new(self.value).with_struct_key_validation(),
+            ValueDeserializer::new(self.value)
+                .with_source(self.source)
+                .with_options(self.options)
+                .with_struct_key_validation(),
             "", // TODO: this should be the variant name
             fields,
             visitor,
@@ -893,10 +1995,357 @@ impl<'de> de::VariantAccess<'de> for TableEnumDeserializer<'de> {
 
 impl<'a> Deserializer<'a> {
     fn new(input: &'a str) -> Deserializer<'a> {
+        Deserializer::with_options(input, Options::default())
+    }
+
+    fn with_options(input: &'a str, options: Options) -> Deserializer<'a> {
+        let mut tokens = Tokenizer::new(input);
+        if options.version == Version::V1_1 {
+            tokens = tokens.with_1_1_escapes();
+        }
         Deserializer {
-            tokens: Tokenizer::new(input),
+            tokens,
             input,
+            file: input,
+            offset: 0,
+            overrides: Vec::new(),
+            env: Rc::from(Vec::new()),
+            options,
+            path: Vec::new(),
+        }
+    }
+
+    // Parses the document and hands its top-level table to `f`.
+    fn deserialize_document<F, R>(&mut self, f: F) -> Result<R, Box<Error>>
+    where
+        F: FnOnce(MapVisitor<'a, '_>) -> Result<R, Box<Error>>,
+    {
+        let mut tables = self.tables()?;
+        self.apply_overrides(&mut tables)?;
+        let table_indices = build_table_indices(&tables);
+        let table_pindices = build_table_pindices(&tables);
+
+        let res = f(MapVisitor {
+            values: Vec::new().into_iter().peekable(),
+            next_value: None,
+            depth: 0,
+            cur: 0,
+            cur_parent: 0,
+            max: tables.len(),
+            table_indices: &table_indices,
+            table_pindices: &table_pindices,
+            tables: &mut tables,
+            array: false,
+            de: self,
+            keys: HashMap::new(),
+            key_at: None,
+        });
+        res.map_err(|mut err| {
+            // Errors originating from this library (toml), have an offset
+            // attached to them already. Other errors, like those originating
+            // from serde (like "missing field") or from a custom deserializer,
+            // do not have offsets on them. Here, we do a best guess at their
+            // location, by attributing them to the "current table" (the last
+            // item in `tables`).
+            err.fix_offset(|| tables.last().map(|table| table.at));
+            self.fix_location(&mut err);
+            err
+        })
+    }
+
+    fn env_overrides(&mut self, prefix: &str, separator: &str) -> Result<(), Box<Error>> {
+        let mut vars = std::env::vars_os()
+            .filter_map(|(var, value)| Some((var.into_string().ok()?, value.into_string().ok()?)))
+            .filter_map(|(var, value)| {
+                let path = var.strip_prefix(prefix)?.strip_prefix(separator)?;
+                let path = path
+                    .split(separator)
+                    .map(|part| Cow::Owned(part.to_ascii_lowercase()))
+                    .collect::<Vec<Cow<str>>>();
+                if path.iter().any(|part| part.is_empty()) {
+                    return None;
+                }
+                Some((var, path, value))
+            })
+            .collect::<Vec<_>>();
+        vars.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut env = Vec::new();
+        for (var, path, value) in vars {
+            let at = self.input.len() + 1 + self.overrides.len();
+            let mut d = Deserializer::with_options(&value, self.options.clone());
+            let value = match d.standalone_value() {
+                Ok(value) => value.into_owned(at),
+                Err(mut err) => {
+                    err.line = None;
+                    err.env = Some(var);
+                    return Err(err);
+                }
+            };
+            self.overrides.push(Override { path, value });
+            env.push(var);
+        }
+        self.env = Rc::from(env);
+        Ok(())
+    }
+
+    // Stores each environment override into the table whose header is the
+    // longest prefix of the override's key path.
+    fn apply_overrides(&self, tables: &mut Vec<Table<'a>>) -> Result<(), Box<Error>> {
+        if self.overrides.is_empty() {
+            return Ok(());
+        }
+        if !matches!(tables.first(), Some(table) if table.header.is_empty()) {
+            tables.insert(
+                0,
+                Table {
+                    at: 0,
+                    header: Vec::new(),
+                    values: Some(Vec::new()),
+                    array: false,
+                },
+            );
+        }
+        for (i, o) in self.overrides.iter().enumerate() {
+            let at = self.input.len() + 1 + i;
+            let mut stale = stale_tables(tables, &o.path);
+
+            // Tables at or below the path are replaced along with the rest of
+            // the value there.
+            let mut j = 0;
+            while j < tables.len() {
+                if !stale[j] && starts_with(&tables[j].header, &o.path) {
+                    tables.remove(j);
+                    stale.remove(j);
+                } else {
+                    j += 1;
+                }
+            }
+
+            // The override goes into the deepest table on its path. A table
+            // that only exists as the parent of others, like `a` for `[a.b]`,
+            // is given a header of its own right before them.
+            let depth = tables
+                .iter()
+                .zip(&stale)
+                .filter(|(_, stale)| !**stale)
+                .map(|(table, _)| {
+                    let parts = table.header.iter().zip(&o.path);
+                    parts.take_while(|(part, key)| part.1 == **key).count()
+                })
+                .max()
+                .unwrap_or(0);
+            let path = &o.path[..depth];
+            let found = tables.iter().zip(&stale).rposition(|(table, stale)| {
+                !stale && table.header.len() == depth && starts_with(&table.header, path)
+            });
+            let pos = if let Some(pos) = found {
+                pos
+            } else {
+                let pos = tables
+                    .iter()
+                    .zip(&stale)
+                    .position(|(table, stale)| !stale && starts_with(&table.header, path))
+                    .expect("some table goes through the path");
+                let span = Span { start: at, end: at };
+                let header = path.iter().map(|key| (span, key.clone())).collect();
+                let table = Table {
+                    at,
+                    header,
+                    values: Some(Vec::new()),
+                    array: false,
+                };
+                tables.insert(pos, table);
+                pos
+            };
+            let values = tables[pos]
+                .values
+                .as_mut()
+                .expect("Unable to read table values");
+            self.set_dotted_key(&o.path[depth..], o.value.clone(), values, at)
+                .map_err(|mut err| {
+                    self.fix_location(&mut err);
+                    err
+                })?;
+        }
+        Ok(())
+    }
+
+    // Like `add_dotted_key`, but replaces an existing value instead of
+    // leaving a duplicate key behind.
+    fn set_dotted_key(
+        &self,
+        key_parts: &[Cow<'static, str>],
+        value: Value<'a>,
+        values: &mut Vec<TablePair<'a>>,
+        at: usize,
+    ) -> Result<(), Box<Error>> {
+        let span = Span { start: at, end: at };
+        let (key, rest) = key_parts.split_first().expect("key path is not empty");
+        let existing = values.iter_mut().find(|(k, _)| k.1 == *key);
+        match existing {
+            Some((_, slot)) if rest.is_empty() => *slot = value,
+            Some((
+                _,
+                Value {
+                    e: E::InlineTable(v) | E::DottedTable(v),
+                    ..
+                },
+            )) => self.set_dotted_key(rest, value, v, at)?,
+            Some(_) => {
+                return Err(Error::from_kind(Some(at), ErrorKind::DottedKeyInvalidType));
+            }
+            None => {
+                let key_parts = key_parts.iter().map(|key| (span, key.clone())).collect();
+                self.add_dotted_key(key_parts, value, values, &mut Vec::new())?;
+            }
+        }
+        Ok(())
+    }
+
+    // Parses input consisting of nothing but a single value.
+    fn standalone_value(&mut self) -> Result<Value<'a>, Box<Error>> {
+        self.eat_whitespace();
+        let value = self.value()?;
+        self.eat_whitespace();
+        match self.peek()? {
+            Some((Span { start, .. }, token)) => Err(self.error(
+                start,
+                ErrorKind::Wanted {
+                    expected: "end of value",
+                    found: token.describe(),
+                },
+            )),
+            None => Ok(value),
+        }
+    }
+
+    // Headers may add sub-tables to a table defined with dotted keys, as in:
+    //
+    //      [fruit]
+    //      apple.color = "red"
+    //
+    //      [fruit.apple.texture]
+    //      smooth = true
+    //
+    // The `MapVisitor` only nests tables defined by headers, so such tables
+    // are moved into the values of the dotted table they extend.
+    fn extend_dotted_tables(&self, tables: &mut Vec<Table<'a>>) -> Result<(), Box<Error>> {
+        let mut implicit = HashSet::new();
+        let mut arrays = HashSet::new();
+        let mut i = 0;
+        while i < tables.len() {
+            if let Some((j, k)) = dotted_owner(&tables[..i], &tables[i].header) {
+                let table = tables.remove(i);
+                let values = tables[j].values.as_mut().unwrap();
+                self.move_into_dotted(table, k, values, &mut implicit, &mut arrays)?;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(())
+    }
+
+    // Moves `table` into the dotted table at `table.header[k]` in `values`.
+    // `implicit` holds the paths of tables created only as the parent of
+    // another one, `arrays` those of arrays created by `[[header]]`s.
+    fn move_into_dotted(
+        &self,
+        table: Table<'a>,
+        k: usize,
+        mut values: &mut Vec<TablePair<'a>>,
+        implicit: &mut HashSet<Vec<Cow<'a, str>>>,
+        arrays: &mut HashSet<Vec<Cow<'a, str>>>,
+    ) -> Result<(), Box<Error>> {
+        let path = |m: usize| {
+            table.header[..=m]
+                .iter()
+                .map(|v| v.1.clone())
+                .collect::<Vec<_>>()
+        };
+        let duplicate_key =
+            |span: Span, key: &str| self.error(span.start, ErrorKind::DuplicateKey(key.to_owned()));
+        let last = table.header.len() - 1;
+
+        for (m, (span, key)) in table.header.iter().enumerate().take(last).skip(k) {
+            let path = path(m);
+            let pos = if let Some(pos) = values.iter().position(|(k, _)| k.1 == *key) {
+                pos
+            } else {
+                implicit.insert(path.clone());
+                let e = E::DottedTable(Vec::new());
+                let (start, end) = (span.start, span.end);
+                values.push(((*span, key.clone()), Value { e, start, end }));
+                values.len() - 1
+            };
+            let slot = &mut values[pos].1;
+            let slot = if arrays.contains(&path) && matches!(slot.e, E::Array(_)) {
+                match &mut slot.e {
+                    E::Array(array) => array.last_mut().unwrap(),
+                    _ => unreachable!(),
+                }
+            } else {
+                slot
+            };
+            values = match &mut slot.e {
+                E::DottedTable(values) => values,
+                _ => return Err(duplicate_key(*span, key)),
+            };
+        }
+
+        let (span, key) = &table.header[last];
+        let path = path(last);
+        let pos = values.iter().position(|(k, _)| k.1 == *key);
+        let new = table.values.unwrap_or_default();
+        if table.array {
+            let pos = match pos {
+                Some(pos) if arrays.contains(&path) => pos,
+                Some(_) => return Err(duplicate_key(*span, key)),
+                None => {
+                    let e = E::Array(Vec::new());
+                    let (start, end) = (span.start, span.end);
+                    values.push(((*span, key.clone()), Value { e, start, end }));
+                    values.len() - 1
+                }
+            };
+            // Tables nested in the previous element of the array are
+            // unrelated to those in the new one.
+            implicit.retain(|p| !p.starts_with(&path));
+            arrays.retain(|p| p.len() == path.len() || !p.starts_with(&path));
+            arrays.insert(path);
+            if let E::Array(array) = &mut values[pos].1.e {
+                array.push(Value {
+                    e: E::DottedTable(new),
+                    start: table.at,
+                    end: table.at,
+                });
+            }
+        } else if let Some(pos) = pos {
+            // A table implicitly created by an earlier header may be defined
+            // once, anything else is a duplicate.
+            if !implicit.remove(&path) {
+                let name = path.join(".");
+                return Err(self.error(table.at, ErrorKind::DuplicateTable(name)));
+            }
+            let existing = match &mut values[pos].1.e {
+                E::DottedTable(existing) => existing,
+                _ => unreachable!(),
+            };
+            for ((span, key), value) in new {
+                if existing.iter().any(|(k, _)| k.1 == key) {
+                    return Err(duplicate_key(span, &key));
+                }
+                existing.push(((span, key), value));
+            }
+        } else {
+            let value = Value {
+                e: E::DottedTable(new),
+                start: table.at,
+                end: table.at,
+            };
+            values.push(((*span, key.clone()), value));
         }
+        Ok(())
     }
 
     fn tables(&mut self) -> Result<Vec<Table<'a>>, Box<Error>> {
@@ -931,18 +2380,22 @@ impl<'a> Deserializer<'a> {
                             None => break,
                         }
                     }
+                    self.path = cur_table.header.iter().map(|p| p.1.clone()).collect();
                 }
                 Line::KeyValue(key, value) => {
                     if cur_table.values.is_none() {
                         cur_table.values = Some(Vec::new());
                     }
-                    self.add_dotted_key(key, value, cur_table.values.as_mut().unwrap())?;
+                    let values = cur_table.values.as_mut().unwrap();
+                    self.add_dotted_key(key, value, values, &mut Vec::new())?;
                 }
             }
         }
+        self.path.clear();
         if !cur_table.header.is_empty() || cur_table.values.is_some() {
             tables.push(cur_table);
         }
+        self.extend_dotted_tables(&mut tables)?;
         Ok(tables)
     }
 
@@ -984,7 +2437,7 @@ impl<'a> Deserializer<'a> {
         self.expect(Token::Equals)?;
         self.eat_whitespace();
 
-        let value = self.value()?;
+        let value = self.keyed_value(&key)?;
         self.eat_whitespace();
         if !self.eat_comment()? {
             self.eat_newline_or_eof()?;
@@ -993,6 +2446,16 @@ impl<'a> Deserializer<'a> {
         Ok(Line::KeyValue(key, value))
     }
 
+    // Parses the value of `key` in the current table, which is where the
+    // entries of an inline table in it belong.
+    fn keyed_value(&mut self, key: &[(Span, Cow<'a, str>)]) -> Result<Value<'a>, Box<Error>> {
+        let len = self.path.len();
+        self.path.extend(key.iter().map(|p| p.1.clone()));
+        let value = self.value();
+        self.path.truncate(len);
+        value
+    }
+
     fn value(&mut self) -> Result<Value<'a>, Box<Error>> {
         let at = self.tokens.current();
         let value = match self.next()? {
@@ -1058,68 +2521,90 @@ impl<'a> Deserializer<'a> {
         }
     }
 
-    fn number(&mut self, Span { start, end }: Span, s: &'a str) -> Result<Value<'a>, Box<Error>> {
-        let to_integer = |f| Value {
-            e: E::Integer(f),
-            start,
-            end,
+    fn number(&mut self, span: Span, s: &'a str) -> Result<Value<'a>, Box<Error>> {
+        let e = self.number_e(s);
+        let end = self.tokens.current();
+        // Keep the number's text around for `Number`, including for numbers
+        // too large for `i64` or `f64`.
+        #[cfg(feature = "arbitrary_precision")]
+        let e = {
+            let lexeme = Cow::Borrowed(&self.input[span.start..end]);
+            match e {
+                Ok(e) => Ok(E::Number(lexeme, Some(Box::new(e)))),
+                Err(err)
+                    if matches!(
+                        err.kind,
+                        ErrorKind::IntegerTooLarge
+                            | ErrorKind::IntegerTooSmall
+                            | ErrorKind::FloatOutOfRange
+                    ) =>
+                {
+                    Ok(E::Number(lexeme, None))
+                }
+                Err(err) => Err(err),
+            }
         };
+        Ok(Value {
+            e: e?,
+            start: span.start,
+            end,
+        })
+    }
+
+    fn number_e(&mut self, s: &'a str) -> Result<E<'a>, Box<Error>> {
+        // Only decimal numbers may have a sign.
+        if let Some(digits) = s.strip_prefix('-') {
+            if is_prefixed_integer(digits) {
+                let at = self.tokens.substr_offset(s);
+                return Err(self.error(at, ErrorKind::SignedPrefixedInteger));
+            }
+        }
+        if is_prefixed_integer(s) {
+            let digits = &s[2..];
+            let plus = digits.is_empty() && matches!(self.peek()?, Some((_, Token::Plus)));
+            if plus || digits.starts_with('-') {
+                let at = self.tokens.substr_offset(s) + 2;
+                return Err(self.error(at, ErrorKind::SignAfterPrefix));
+            }
+        }
         if let Some(s) = s.strip_prefix("0x") {
-            self.integer(s, 16).map(to_integer)
+            self.integer(s, 16).map(E::Integer)
         } else if let Some(s) = s.strip_prefix("0o") {
-            self.integer(s, 8).map(to_integer)
+            self.integer(s, 8).map(E::Integer)
         } else if let Some(s) = s.strip_prefix("0b") {
-            self.integer(s, 2).map(to_integer)
+            self.integer(s, 2).map(E::Integer)
         } else if s.contains('e') || s.contains('E') {
-            self.float(s, None).map(|f| Value {
-                e: E::Float(f),
-                start,
-                end,
-            })
+            self.float(s, None).map(E::Float)
         } else if self.eat(Token::Period)? {
-            let at = self.tokens.current();
+            let period = self.tokens.current() - 1;
             match self.next()? {
-                Some((Span { start, end }, Token::Keylike(after))) => {
-                    self.float(s, Some(after)).map(|f| Value {
-                        e: E::Float(f),
-                        start,
-                        end,
-                    })
+                Some((_, Token::Keylike(after)))
+                    if after.starts_with(|c: char| c.is_ascii_digit() || c == '_') =>
+                {
+                    self.float(s, Some(after)).map(E::Float)
                 }
-                _ => Err(self.error(at, ErrorKind::NumberInvalid)),
+                _ => Err(self.error(period, ErrorKind::MissingFraction)),
             }
         } else if s == "inf" {
-            Ok(Value {
-                e: E::Float(f64::INFINITY),
-                start,
-                end,
-            })
+            Ok(E::Float(f64::INFINITY))
         } else if s == "-inf" {
-            Ok(Value {
-                e: E::Float(f64::NEG_INFINITY),
-                start,
-                end,
-            })
+            Ok(E::Float(f64::NEG_INFINITY))
         } else if s == "nan" {
-            Ok(Value {
-                e: E::Float(f64::NAN.copysign(1.0)),
-                start,
-                end,
-            })
+            Ok(E::Float(f64::NAN.copysign(1.0)))
         } else if s == "-nan" {
-            Ok(Value {
-                e: E::Float(f64::NAN.copysign(-1.0)),
-                start,
-                end,
-            })
+            Ok(E::Float(f64::NAN.copysign(-1.0)))
         } else {
-            self.integer(s, 10).map(to_integer)
+            self.integer(s, 10).map(E::Integer)
         }
     }
 
     fn number_leading_plus(&mut self, Span { start, .. }: Span) -> Result<Value<'a>, Box<Error>> {
         let start_token = self.tokens.current();
         match self.next()? {
+            // Only decimal numbers may have a sign.
+            Some((_, Token::Keylike(s))) if is_prefixed_integer(s) => {
+                Err(self.error(start, ErrorKind::SignedPrefixedInteger))
+            }
             Some((Span { end, .. }, Token::Keylike(s))) => self.number(Span { start, end }, s),
             _ => Err(self.error(start_token, ErrorKind::NumberInvalid)),
         }
@@ -1133,8 +2618,17 @@ impl<'a> Deserializer<'a> {
         if !suffix.is_empty() {
             return Err(self.error(start, ErrorKind::NumberInvalid));
         }
-        i64::from_str_radix(prefix.replace('_', "").trim_start_matches('+'), radix)
-            .map_err(|_e| self.error(start, ErrorKind::NumberInvalid))
+        // The digits have been validated already, so this can only overflow.
+        // Point at the `0x`, `0o` or `0b` of a prefixed integer.
+        let literal = if allow_sign { start } else { start - 2 };
+        i64::from_str_radix(prefix.replace('_', "").trim_start_matches('+'), radix).map_err(|_e| {
+            let kind = if prefix.starts_with('-') {
+                ErrorKind::IntegerTooSmall
+            } else {
+                ErrorKind::IntegerTooLarge
+            };
+            self.error(literal, kind)
+        })
     }
 
     fn parse_integer(
@@ -1147,7 +2641,7 @@ impl<'a> Deserializer<'a> {
         let start = self.tokens.substr_offset(s);
 
         let mut first = true;
-        let mut first_zero = false;
+        let mut first_zero = None;
         let mut underscore = false;
         let mut end = s.len();
         for (i, c) in s.char_indices() {
@@ -1157,15 +2651,26 @@ impl<'a> Deserializer<'a> {
             }
 
             if c == '0' && first {
-                first_zero = true;
+                first_zero = Some(at);
             } else if c.is_digit(radix) {
-                if !first && first_zero && !allow_leading_zeros {
-                    return Err(self.error(at, ErrorKind::NumberInvalid));
+                if !first && !allow_leading_zeros {
+                    if let Some(zero) = first_zero {
+                        // Local times and dates, which are not supported, also
+                        // start with a zero.
+                        let rest =
+                            self.input[at..].trim_start_matches(|c: char| c.is_ascii_digit());
+                        let kind = if rest.starts_with(':') || rest.starts_with('-') {
+                            ErrorKind::NumberInvalid
+                        } else {
+                            ErrorKind::LeadingZero
+                        };
+                        return Err(self.error(zero, kind));
+                    }
                 }
                 underscore = false;
-            } else if c == '_' && first {
-                return Err(self.error(at, ErrorKind::NumberInvalid));
-            } else if c == '_' && !underscore {
+            } else if c == '_' && (first || underscore) {
+                return Err(self.error(at, ErrorKind::MisplacedUnderscore));
+            } else if c == '_' {
                 underscore = true;
             } else {
                 end = i;
@@ -1173,7 +2678,10 @@ impl<'a> Deserializer<'a> {
             }
             first = false;
         }
-        if first || underscore {
+        if underscore {
+            return Err(self.error(start + end - 1, ErrorKind::MisplacedUnderscore));
+        }
+        if first {
             return Err(self.error(start, ErrorKind::NumberInvalid));
         }
         Ok((&s[..end], &s[end..]))
@@ -1195,14 +2703,22 @@ impl<'a> Deserializer<'a> {
 
         let mut exponent = None;
         if suffix.starts_with('e') || suffix.starts_with('E') {
+            let marker = self.tokens.substr_offset(suffix);
+            let is_digits = |s: &str| s.starts_with(|c: char| c.is_ascii_digit() || c == '_');
             let (a, b) = if suffix.len() == 1 {
                 self.eat(Token::Plus)?;
                 match self.next()? {
-                    Some((_, Token::Keylike(s))) => self.parse_integer(s, false, true, 10)?,
-                    _ => return Err(self.error(start, ErrorKind::NumberInvalid)),
+                    Some((_, Token::Keylike(s))) if is_digits(s) => {
+                        self.parse_integer(s, false, true, 10)?
+                    }
+                    _ => return Err(self.error(marker, ErrorKind::MissingExponent)),
                 }
             } else {
-                self.parse_integer(&suffix[1..], true, true, 10)?
+                let digits = &suffix[1..];
+                if !is_digits(digits.strip_prefix('-').unwrap_or(digits)) {
+                    return Err(self.error(marker, ErrorKind::MissingExponent));
+                }
+                self.parse_integer(digits, true, true, 10)?
             };
             if !b.is_empty() {
                 return Err(self.error(start, ErrorKind::NumberInvalid));
@@ -1232,7 +2748,7 @@ impl<'a> Deserializer<'a> {
                 if n.is_finite() {
                     Ok(n)
                 } else {
-                    Err(self.error(start, ErrorKind::NumberInvalid))
+                    Err(self.error(start, ErrorKind::FloatOutOfRange))
                 }
             })
     }
@@ -1240,8 +2756,21 @@ impl<'a> Deserializer<'a> {
     // TODO(#140): shouldn't buffer up this entire table in memory, it'd be
     // great to defer parsing everything until later.
     fn inline_table(&mut self) -> Result<(Span, Vec<TablePair<'a>>), Box<Error>> {
+        // TOML 1.1 allows newlines and comments between the entries of an
+        // inline table, and a trailing comma after the last one.
+        let v1_1 = self.options.version == Version::V1_1;
+        let intermediate = |me: &mut Deserializer| -> Result<(), Box<Error>> {
+            loop {
+                me.eat_whitespace();
+                if !v1_1 || !me.eat(Token::Newline)? && !me.eat_comment()? {
+                    break;
+                }
+            }
+            Ok(())
+        };
+
         let mut ret = Vec::new();
-        self.eat_whitespace();
+        intermediate(self)?;
         if let Some(span) = self.eat_spanned(Token::RightBrace)? {
             return Ok((span, ret));
         }
@@ -1250,15 +2779,20 @@ impl<'a> Deserializer<'a> {
             self.eat_whitespace();
             self.expect(Token::Equals)?;
             self.eat_whitespace();
-            let value = self.value()?;
-            self.add_dotted_key(key, value, &mut ret)?;
+            let value = self.keyed_value(&key)?;
+            self.add_dotted_key(key, value, &mut ret, &mut Vec::new())?;
 
-            self.eat_whitespace();
+            intermediate(self)?;
             if let Some(span) = self.eat_spanned(Token::RightBrace)? {
                 return Ok((span, ret));
             }
             self.expect(Token::Comma)?;
-            self.eat_whitespace();
+            intermediate(self)?;
+            if v1_1 {
+                if let Some(span) = self.eat_spanned(Token::RightBrace)? {
+                    return Ok((span, ret));
+                }
+            }
         }
     }
 
@@ -1322,14 +2856,25 @@ impl<'a> Deserializer<'a> {
     ///   `vec![Cow::Borrowed("part"), Cow::Borrowed("one")].`
     /// * `value`: The parsed value.
     /// * `values`: The `Vec` to store the value in.
+    /// * `parents`: The parts of the dotted key that have been handled already.
     fn add_dotted_key(
         &self,
         mut key_parts: Vec<(Span, Cow<'a, str>)>,
         value: Value<'a>,
         values: &mut Vec<TablePair<'a>>,
+        parents: &mut Vec<Cow<'a, str>>,
     ) -> Result<(), Box<Error>> {
         let key = key_parts.remove(0);
         if key_parts.is_empty() {
+            if self.options.duplicate_keys != DuplicateKeys::Error {
+                if let Some(pos) = values.iter().position(|(k, _)| k.1 == key.1) {
+                    self.report_duplicate_key(parents, &values[pos].0, &key);
+                    if self.options.duplicate_keys == DuplicateKeys::LastWins {
+                        values[pos] = (key, value);
+                    }
+                    return Ok(());
+                }
+            }
             values.push((key, value));
             return Ok(());
         }
@@ -1341,7 +2886,8 @@ impl<'a> Deserializer<'a> {
                     ..
                 },
             )) => {
-                return self.add_dotted_key(key_parts, value, v);
+                parents.push(key.1);
+                return self.add_dotted_key(key_parts, value, v, parents);
             }
             Some(&mut (_, Value { start, .. })) => {
                 return Err(self.error(start, ErrorKind::DottedKeyInvalidType));
@@ -1354,6 +2900,7 @@ impl<'a> Deserializer<'a> {
             start: value.start,
             end: value.end,
         };
+        parents.push(key.1.clone());
         values.push((key, table_values));
         let last_i = values.len() - 1;
         if let (
@@ -1364,11 +2911,30 @@ impl<'a> Deserializer<'a> {
             },
         ) = values[last_i]
         {
-            self.add_dotted_key(key_parts, value, v)?;
+            self.add_dotted_key(key_parts, value, v, parents)?;
         }
         Ok(())
     }
 
+    // Passes a duplicate key that is being let through to the callback, if
+    // there is one, along with the keys of the tables it is in.
+    fn report_duplicate_key(
+        &self,
+        parents: &[Cow<'a, str>],
+        first: &(Span, Cow<'a, str>),
+        second: &(Span, Cow<'a, str>),
+    ) {
+        if let Some(callback) = &self.options.on_duplicate_key {
+            let range = |span: Span| self.offset + span.start..self.offset + span.end;
+            let path = self.path.iter().chain(parents).chain(Some(&second.1));
+            (callback.0)(&DuplicateKey {
+                key: path.map(|key| &**key).collect::<Vec<_>>().join("."),
+                first: range(first.0),
+                second: range(second.0),
+            });
+        }
+    }
+
     fn eat_whitespace(&mut self) {
         self.tokens.eat_whitespace();
     }
@@ -1440,6 +3006,16 @@ impl<'a> Deserializer<'a> {
         }
     }
 
+    fn fix_location(&self, err: &mut Error) {
+        if let Some(at) = err.at {
+            if let Some(i) = at.checked_sub(self.input.len() + 1) {
+                err.env = Some(self.env[i].clone());
+                return;
+            }
+        }
+        err.fix_linecol(|at| self.to_linecol(at));
+    }
+
     fn error(&self, at: usize, kind: ErrorKind) -> Box<Error> {
         let mut err = Error::from_kind(Some(at), kind);
         err.fix_linecol(|at| self.to_linecol(at));
@@ -1450,18 +3026,46 @@ impl<'a> Deserializer<'a> {
     ///
     /// All indexes are 0-based.
     fn to_linecol(&self, offset: usize) -> (usize, usize) {
-        let mut cur = 0;
-        // Use split_terminator instead of lines so that if there is a `\r`, it
-        // is included in the offset calculation. The `+1` values below account
-        // for the `\n`.
-        for (i, line) in self.input.split_terminator('\n').enumerate() {
-            if cur + line.len() + 1 > offset {
-                return (i, offset - cur);
-            }
-            cur += line.len() + 1;
+        to_linecol(self.file, self.offset + offset)
+    }
+
+    fn source(&self) -> Source<'a> {
+        Source {
+            file: self.file,
+            offset: self.offset,
+            len: self.input.len(),
+            env: Rc::clone(&self.env),
+        }
+    }
+}
+
+/// Converts a byte offset in `file` to a (line, column) pair
+///
+/// All indexes are 0-based.
+pub(crate) fn to_linecol(file: &str, offset: usize) -> (usize, usize) {
+    let mut cur = 0;
+    // Use split_terminator instead of lines so that if there is a `\r`, it is
+    // included in the offset calculation. The `+1` values below account for
+    // the `\n`.
+    for (i, line) in file.split_terminator('\n').enumerate() {
+        if cur + line.len() + 1 > offset {
+            return (i, offset - cur);
         }
-        (self.input.lines().count(), 0)
+        cur += line.len() + 1;
     }
+    (file.lines().count(), 0)
+}
+
+// The input that the spans of a `Value` refer to: bytes `offset..offset + len`
+// of `file`. Values taken from environment variables have spans past `len`.
+// The names of the environment variables that those values were taken from are
+// in `env`, in order.
+#[derive(Clone)]
+pub(crate) struct Source<'a> {
+    pub(crate) file: &'a str,
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+    pub(crate) env: Rc<[String]>,
 }
 
 impl Error {
@@ -1477,6 +3081,7 @@ impl Error {
             at,
             message: String::new(),
             key: Vec::new(),
+            env: None,
         })
     }
 
@@ -1488,6 +3093,7 @@ impl Error {
             at,
             message: s,
             key: Vec::new(),
+            env: None,
         })
     }
 
@@ -1556,16 +3162,38 @@ impl Display for Error {
                 write!(f, "expected {}, found {}", expected, found)?;
             }
             ErrorKind::NumberInvalid => "invalid number".fmt(f)?,
+            ErrorKind::IntegerTooLarge => "integer literal exceeds i64::MAX".fmt(f)?,
+            ErrorKind::IntegerTooSmall => "integer literal is less than i64::MIN".fmt(f)?,
+            ErrorKind::FloatOutOfRange => "float literal is out of range for f64".fmt(f)?,
+            ErrorKind::LeadingZero => "leading zeros are not allowed in decimal integers".fmt(f)?,
+            ErrorKind::MisplacedUnderscore => "underscore must be between digits".fmt(f)?,
+            ErrorKind::MissingFraction => "expected digits after the decimal point".fmt(f)?,
+            ErrorKind::MissingExponent => "expected digits after the exponent".fmt(f)?,
+            ErrorKind::SignedPrefixedInteger => {
+                "only decimal numbers may have a sign".fmt(f)?;
+            }
+            ErrorKind::SignAfterPrefix => {
+                "a sign cannot follow the prefix of an integer".fmt(f)?;
+            }
             ErrorKind::DuplicateTable(ref s) => {
                 write!(f, "redefinition of table `{}`", s)?;
             }
             ErrorKind::DuplicateKey(ref s) => {
                 write!(f, "duplicate key: `{}`", s)?;
             }
+            ErrorKind::KeyCollision {
+                ref first,
+                ref second,
+                ref normalized,
+            } => write!(
+                f,
+                "keys `{}` and `{}` are both normalized to `{}`",
+                first, second, normalized,
+            )?,
             ErrorKind::RedefineAsArray => "table redefined as array".fmt(f)?,
             ErrorKind::MultilineStringKey => "multiline strings are not allowed for key".fmt(f)?,
             ErrorKind::Custom => self.message.fmt(f)?,
-            ErrorKind::ExpectedTuple(l) => write!(f, "expected table with length {}", l)?,
+            ErrorKind::ExpectedTuple(l) => write!(f, "expected tuple with length {}", l)?,
             ErrorKind::ExpectedTupleIndex {
                 expected,
                 ref found,
@@ -1599,7 +3227,9 @@ impl Display for Error {
             write!(f, "`")?;
         }
 
-        if let Some(line) = self.line {
+        if let Some(var) = &self.env {
+            write!(f, " in environment variable `{}`", var)?;
+        } else if let Some(line) = self.line {
             write!(f, " at line {} column {}", line + 1, self.col + 1)?;
         }
 
@@ -1661,15 +3291,61 @@ impl<'a> Header<'a> {
     }
 }
 
-#[derive(Debug)]
-struct Value<'a> {
-    e: E<'a>,
-    start: usize,
-    end: usize,
+#[derive(Debug, Clone)]
+pub(crate) struct Value<'a> {
+    pub(crate) e: E<'a>,
+    pub(crate) start: usize,
+    pub(crate) end: usize,
 }
 
-#[derive(Debug)]
-enum E<'a> {
+impl<'a> Value<'a> {
+    // Detaches the value from the input it was parsed from, attributing it and
+    // everything inside it to the position `at`.
+    pub(crate) fn into_owned(self, at: usize) -> Value<'static> {
+        self.detach(Some(at))
+    }
+
+    // Detaches the value from the input it was parsed from, keeping the
+    // positions of everything inside it.
+    pub(crate) fn into_static(self) -> Value<'static> {
+        self.detach(None)
+    }
+
+    fn detach(self, at: Option<usize>) -> Value<'static> {
+        let start = at.unwrap_or(self.start);
+        let end = at.unwrap_or(self.end);
+        let table = |values: Vec<TablePair<'a>>| {
+            values
+                .into_iter()
+                .map(|((span, key), value)| {
+                    let span = match at {
+                        Some(at) => Span { start: at, end: at },
+                        None => span,
+                    };
+                    ((span, Cow::Owned(key.into_owned())), value.detach(at))
+                })
+                .collect()
+        };
+        let e = match self.e {
+            E::Integer(i) => E::Integer(i),
+            E::Float(f) => E::Float(f),
+            E::Boolean(b) => E::Boolean(b),
+            E::String(s) => E::String(Cow::Owned(s.into_owned())),
+            E::Array(values) => E::Array(values.into_iter().map(|v| v.detach(at)).collect()),
+            E::InlineTable(values) => E::InlineTable(table(values)),
+            E::DottedTable(values) => E::DottedTable(table(values)),
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(lexeme, e) => {
+                let e = e.map(|e| Box::new(Value { e: *e, start, end }.detach(at).e));
+                E::Number(Cow::Owned(lexeme.into_owned()), e)
+            }
+        };
+        Value { e, start, end }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum E<'a> {
     Integer(i64),
     Float(f64),
     Boolean(bool),
@@ -1677,6 +3353,10 @@ enum E<'a> {
     Array(Vec<Value<'a>>),
     InlineTable(Vec<TablePair<'a>>),
     DottedTable(Vec<TablePair<'a>>),
+    // The text of an integer or float, along with its value unless it is out
+    // of range.
+    #[cfg(feature = "arbitrary_precision")]
+    Number(Cow<'a, str>, Option<Box<E<'a>>>),
 }
 
 impl<'a> E<'a> {
@@ -1689,6 +3369,10 @@ impl<'a> E<'a> {
             E::Array(..) => "array",
             E::InlineTable(..) => "inline table",
             E::DottedTable(..) => "dotted table",
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(_, Some(ref e)) => e.type_name(),
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(_, None) => "number",
         }
     }
 }
@@ -0,0 +1,96 @@
+use std::ops::Range;
+
+/// Returns the TOML front matter at the start of a file, if there is any.
+///
+/// Two formats are recognized:
+///
+/// - A block between two `+++` lines, as used by static site generators like
+///   Hugo and Zola for Markdown files.
+///
+///   ```text
+///   +++
+///   title = "Hello"
+///   +++
+///   ```
+///
+/// - A block opened by a line of three or more dashes, optionally followed by
+///   `cargo` or `toml`, and closed by a line of the same number of dashes, as
+///   used by single-file Rust scripts. The block may follow a `#!` line and
+///   blank lines. Note that static site generators use a bare `---` fence for
+///   YAML, which is then reported as a TOML syntax error by
+///   [`from_front_matter`](crate::from_front_matter).
+///
+///   ```text
+///   #!/usr/bin/env cargo
+///   ---cargo
+///   [dependencies]
+///   regex = "1"
+///   ---
+///   ```
+///
+/// The returned string slice is the content between the fences, not including
+/// the fences themselves.
+pub fn front_matter(s: &str) -> Option<&str> {
+    find(s).map(|range| &s[range])
+}
+
+// Finds the range of `s` between the opening and closing fence lines.
+pub(crate) fn find(s: &str) -> Option<Range<usize>> {
+    let mut lines = Lines {
+        s,
+        pos: if s.starts_with('\u{feff}') { 3 } else { 0 },
+    };
+
+    let (_, mut line) = lines.next()?;
+    if line.trim_end() == "+++" {
+        return lines.block("+++");
+    }
+
+    if line.starts_with("#!") && !line.starts_with("#![") {
+        line = lines.next()?.1;
+    }
+    while line.trim().is_empty() {
+        line = lines.next()?.1;
+    }
+    let dashes = line.len() - line.trim_start_matches('-').len();
+    if dashes < 3 {
+        return None;
+    }
+    match line[dashes..].trim() {
+        "" | "cargo" | "toml" => lines.block(&line[..dashes]),
+        _ => None,
+    }
+}
+
+struct Lines<'a> {
+    s: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lines<'a> {
+    // Returns the offset and content of the next line, without its newline.
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        if self.pos == self.s.len() {
+            return None;
+        }
+        let start = self.pos;
+        let rest = &self.s[start..];
+        let (line, len) = match rest.find('\n') {
+            Some(i) => (&rest[..i], i + 1),
+            None => (rest, rest.len()),
+        };
+        self.pos += len;
+        Some((start, line))
+    }
+
+    // Finds the closing `fence` line, returning the range of lines before it.
+    fn block(&mut self, fence: &str) -> Option<Range<usize>> {
+        let start = self.pos;
+        while let Some((end, line)) = self.next() {
+            if line.trim_end() == fence {
+                return Some(start..end);
+            }
+        }
+        None
+    }
+}
@@ -109,7 +109,7 @@
 //!
 //! # Spec compatibility
 //!
-//! TOML v0.5.0.
+//! TOML v1.0.0.
 //!
 //! TOML's date and time syntax are not supported.
 
@@ -117,6 +117,7 @@
 #![deny(missing_docs)]
 #![allow(
     clippy::bool_to_int_with_if,
+    clippy::derivable_impls,
     clippy::elidable_lifetime_names,
     clippy::let_underscore_untyped,
     clippy::manual_let_else,
@@ -136,9 +137,26 @@
 
 mod de;
 mod error;
+mod front_matter;
+#[cfg(feature = "arbitrary_precision")]
+mod number;
+mod options;
+mod raw;
 mod ser;
+pub mod style;
 mod tokens;
 
-pub use crate::de::{from_slice, from_str};
+pub use crate::de::{
+    from_front_matter, from_slice, from_str, from_str_into, from_str_with_env, from_value_str,
+};
 pub use crate::error::Error;
-pub use crate::ser::to_string;
+pub use crate::front_matter::front_matter;
+#[cfg(feature = "arbitrary_precision")]
+pub use crate::number::Number;
+pub use crate::options::{
+    DuplicateKey, DuplicateKeys, KeyNormalization, NullPolicy, Options, Version,
+};
+pub use crate::raw::RawValue;
+pub use crate::ser::{
+    to_string, to_string_array_entry_at, to_string_at, to_string_canonical, to_value_string,
+};
@@ -0,0 +1,119 @@
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+pub(crate) const TOKEN: &str = "$__basic_toml_private_Number";
+
+/// A TOML integer or float, kept exactly as it was written.
+///
+/// Deserializing a `Number` captures the text of the number from the TOML
+/// document, including numbers too large for `i64` or `f64`, so that types
+/// such as decimals and bignums can parse it without losing precision.
+/// Serializing a `Number` writes the same text back out unchanged.
+///
+/// ```
+/// use basic_toml::Number;
+/// use serde::{Deserialize, Serialize};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Invoice {
+///     total: Number,
+/// }
+///
+/// let toml = "total = 12345678901234567890.000000000000000001\n";
+///
+/// let invoice: Invoice = basic_toml::from_str(toml).unwrap();
+/// assert_eq!(invoice.total.as_str(), "12345678901234567890.000000000000000001");
+/// assert_eq!(basic_toml::to_string(&invoice).unwrap(), toml);
+/// ```
+///
+/// This type is only available with the `arbitrary_precision` feature.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Number {
+    lexeme: String,
+}
+
+impl Number {
+    /// The number as written in TOML, such as `1_000`, `0xff` or `6.02e23`.
+    pub fn as_str(&self) -> &str {
+        &self.lexeme
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str(&self.lexeme)
+    }
+}
+
+impl FromStr for Number {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = crate::de::parse_number(s)?;
+        Ok(Number {
+            lexeme: s[value.start..value.end].to_owned(),
+        })
+    }
+}
+
+impl Serialize for Number {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(TOKEN, &self.lexeme)
+    }
+}
+
+impl<'de> Deserialize<'de> for Number {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_newtype_struct(TOKEN, NumberVisitor)
+    }
+}
+
+struct NumberVisitor;
+
+impl<'de> Visitor<'de> for NumberVisitor {
+    type Value = Number;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML number")
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Number, E> {
+        Ok(Number {
+            lexeme: v.to_string(),
+        })
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Number, E> {
+        Ok(Number {
+            lexeme: v.to_string(),
+        })
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Number, E> {
+        Ok(Number {
+            lexeme: crate::ser::float_to_string(v),
+        })
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Number, E>
+    where
+        E: de::Error,
+    {
+        v.parse().map_err(de::Error::custom)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Number, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
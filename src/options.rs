@@ -0,0 +1,482 @@
+use crate::raw::RawValue;
+use serde::{de, ser};
+use std::fmt::{self, Debug};
+use std::ops::Range;
+use std::sync::Arc;
+
+/// Settings for parsing and producing TOML.
+///
+/// The free functions such as [`from_str`](crate::from_str) and
+/// [`to_string`](crate::to_string) use the default settings. Use `Options` to
+/// change them:
+///
+/// ```
+/// use basic_toml::{Options, Version};
+/// use serde_json::{json, Value};
+///
+/// let toml = "point = {\n    x = 1,\n    y = 2,\n}\n";
+///
+/// let value: Value = Options::new().version(Version::V1_1).from_str(toml).unwrap();
+/// assert_eq!(value, json!({ "point": { "x": 1, "y": 2 } }));
+///
+/// assert!(basic_toml::from_str::<Value>(toml).is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub(crate) version: Version,
+    pub(crate) nulls: Option<NullPolicy>,
+    pub(crate) coerce: bool,
+    pub(crate) keys: Option<KeyNormalization>,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    pub(crate) on_duplicate_key: Option<DuplicateKeyCallback>,
+}
+
+/// The version of the TOML specification to accept.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Version {
+    /// [TOML v1.0.0](https://toml.io/en/v1.0.0). This is the default.
+    V1_0,
+
+    /// [TOML v1.1.0](https://toml.io/en/v1.1.0), which in addition allows:
+    ///
+    /// - newlines, comments and a trailing comma inside inline tables,
+    /// - the `\e` escape for the escape character,
+    /// - the `\xHH` escape for codepoints up to U+00FF.
+    ///
+    /// Times without seconds are also new in 1.1, but this crate does not
+    /// support TOML's date and time syntax in either version.
+    V1_1,
+}
+
+/// What to do when serializing `None`, or a null from a format such as JSON,
+/// which TOML has no way to represent.
+///
+/// Without a policy, `None` values of table entries are left out of the output
+/// and any other `None` fails serialization. With a policy, the unit value `()`
+/// is treated the same as `None`, since that is how formats such as JSON
+/// represent null. Unit structs are written as empty tables either way.
+///
+/// ```
+/// use basic_toml::{NullPolicy, Options};
+/// use serde_json::json;
+///
+/// let value = json!({ "list": [1, null, 3], "table": { "a": null, "b": 2 } });
+///
+/// let options = Options::new().null_policy(NullPolicy::Skip);
+/// let toml = options.to_string(&value).unwrap();
+/// assert_eq!(toml, "list = [1, 3]\n\n[table]\nb = 2\n");
+///
+/// let options = Options::new().null_policy(NullPolicy::Placeholder("".to_owned()));
+/// let toml = options.to_string(&value).unwrap();
+/// assert_eq!(toml, "list = [1, \"\", 3]\n\n[table]\na = \"\"\nb = 2\n");
+///
+/// let options = Options::new().null_policy(NullPolicy::Error);
+/// assert!(options.to_string(&value).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NullPolicy {
+    /// Fail serialization wherever a `None` appears.
+    Error,
+
+    /// Leave `None` values out of tables and arrays. A `None` in place of the
+    /// whole document produces an empty document.
+    Skip,
+
+    /// Write the given string in place of every `None`.
+    Placeholder(String),
+}
+
+/// How to rewrite keys before they are matched against field and variant
+/// names when deserializing.
+///
+/// Keys that only differ before normalization, such as `max-size` and
+/// `max_size` in the same table, are reported as a collision.
+///
+/// ```
+/// use basic_toml::{KeyNormalization, Options};
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Package {
+///     rust_version: String,
+/// }
+///
+/// let toml = "rust-version = \"1.56\"\n";
+///
+/// let options = Options::new().normalize_keys(KeyNormalization::SnakeCase);
+/// let package: Package = options.from_str(toml).unwrap();
+/// assert_eq!(package.rust_version, "1.56");
+///
+/// let toml = "rust-version = \"1.56\"\nrust_version = \"1.60\"\n";
+/// assert!(options.from_str::<Package>(toml).is_err());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyNormalization {
+    /// Replace `-` with `_`, so that `kebab-case` keys match `snake_case`
+    /// names.
+    SnakeCase,
+
+    /// Replace `_` with `-`, so that `snake_case` keys match `kebab-case`
+    /// names.
+    KebabCase,
+
+    /// Convert ASCII letters to lowercase, so that keys match regardless of
+    /// case. Names on the Rust side must be lowercase too, for example using
+    /// `#[serde(rename_all = "lowercase")]` on enums.
+    Lowercase,
+}
+
+impl KeyNormalization {
+    // Returns the normalized key, or None if normalization leaves it as is.
+    pub(crate) fn apply(self, key: &str) -> Option<String> {
+        let (from, to) = match self {
+            KeyNormalization::SnakeCase => ('-', "_"),
+            KeyNormalization::KebabCase => ('_', "-"),
+            KeyNormalization::Lowercase => {
+                return if key.bytes().any(|b| b.is_ascii_uppercase()) {
+                    Some(key.to_ascii_lowercase())
+                } else {
+                    None
+                };
+            }
+        };
+        if key.contains(from) {
+            Some(key.replace(from, to))
+        } else {
+            None
+        }
+    }
+}
+
+/// What to do when a key is given more than once in the same table when
+/// deserializing.
+///
+/// ```
+/// use basic_toml::{DuplicateKeys, Options};
+/// use serde_json::{json, Value};
+///
+/// let toml = "name = \"old\"\nname = \"new\"\n";
+///
+/// let options = Options::new().duplicate_keys(DuplicateKeys::LastWins);
+/// let value: Value = options.from_str(toml).unwrap();
+/// assert_eq!(value, json!({ "name": "new" }));
+///
+/// let options = Options::new().duplicate_keys(DuplicateKeys::FirstWins);
+/// let value: Value = options.from_str(toml).unwrap();
+/// assert_eq!(value, json!({ "name": "old" }));
+///
+/// assert!(basic_toml::from_str::<Value>(toml).is_err());
+/// ```
+///
+/// This only concerns keys with a value, such as `name = "new"`. Defining the
+/// same table twice, or a key as both a value and a table, is always an error.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DuplicateKeys {
+    /// Fail deserialization, as the TOML specification requires. This is the
+    /// default.
+    Error,
+
+    /// Keep the value of the last occurrence of the key.
+    LastWins,
+
+    /// Keep the value of the first occurrence of the key.
+    FirstWins,
+}
+
+/// A key that was given more than once in a table, as reported to the
+/// callback set with [`Options::on_duplicate_key`].
+#[derive(Clone, Debug)]
+pub struct DuplicateKey {
+    pub(crate) key: String,
+    pub(crate) first: Range<usize>,
+    pub(crate) second: Range<usize>,
+}
+
+impl DuplicateKey {
+    /// The full path of the key from the top of the document, with its parts
+    /// joined by `.` as in `dependencies.serde`, and any quotes and escapes
+    /// resolved.
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The byte range of the first occurrence of the key in the input.
+    pub fn first(&self) -> Range<usize> {
+        self.first.clone()
+    }
+
+    /// The byte range of the second occurrence of the key in the input.
+    pub fn second(&self) -> Range<usize> {
+        self.second.clone()
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct DuplicateKeyCallback(pub(crate) Arc<dyn Fn(&DuplicateKey) + Send + Sync>);
+
+impl Debug for DuplicateKeyCallback {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("DuplicateKeyCallback")
+    }
+}
+
+impl Default for DuplicateKeys {
+    fn default() -> Self {
+        DuplicateKeys::Error
+    }
+}
+
+impl Default for Version {
+    fn default() -> Self {
+        Version::V1_0
+    }
+}
+
+impl Options {
+    /// Creates the default settings.
+    pub fn new() -> Self {
+        Options::default()
+    }
+
+    /// Sets the version of the TOML specification to accept.
+    #[must_use]
+    pub fn version(mut self, version: Version) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Sets whether to convert values to the type that is asked for when
+    /// deserializing.
+    ///
+    /// This is off by default. When on, a string is parsed as a boolean,
+    /// integer or float wherever one of those is expected, so `port = "8080"`
+    /// can be deserialized into a `u16`, and a value which is not an array is
+    /// deserialized as an array of one element wherever a sequence is
+    /// expected. This suits values that are edited by hand or that arrive as
+    /// strings, such as from environment variables or form fields.
+    ///
+    /// ```
+    /// use basic_toml::Options;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Config {
+    ///     port: u16,
+    ///     debug: bool,
+    ///     hosts: Vec<String>,
+    /// }
+    ///
+    /// let toml = "port = \"8080\"\ndebug = \"true\"\nhosts = \"localhost\"\n";
+    ///
+    /// let config: Config = Options::new().coerce(true).from_str(toml).unwrap();
+    /// assert_eq!(config.port, 8080);
+    /// assert!(config.debug);
+    /// assert_eq!(config.hosts, ["localhost"]);
+    ///
+    /// assert!(basic_toml::from_str::<Config>(toml).is_err());
+    /// ```
+    #[must_use]
+    pub fn coerce(mut self, coerce: bool) -> Self {
+        self.coerce = coerce;
+        self
+    }
+
+    /// Sets how keys are normalized when deserializing.
+    ///
+    /// The normalized key is what is matched against struct fields, map keys
+    /// and enum variant names. See [`KeyNormalization`].
+    #[must_use]
+    pub fn normalize_keys(mut self, keys: KeyNormalization) -> Self {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Sets what to do when a key is given more than once in a table.
+    ///
+    /// See [`DuplicateKeys`].
+    #[must_use]
+    pub fn duplicate_keys(mut self, duplicate_keys: DuplicateKeys) -> Self {
+        self.duplicate_keys = duplicate_keys;
+        self
+    }
+
+    /// Sets a function to call for every duplicate key that is let through by
+    /// [`DuplicateKeys::LastWins`] or [`DuplicateKeys::FirstWins`], for
+    /// example to log a warning.
+    ///
+    /// ```
+    /// use basic_toml::{DuplicateKeys, Options};
+    /// use serde_json::Value;
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let warnings = Arc::new(Mutex::new(Vec::new()));
+    /// let options = Options::new()
+    ///     .duplicate_keys(DuplicateKeys::FirstWins)
+    ///     .on_duplicate_key({
+    ///         let warnings = Arc::clone(&warnings);
+    ///         move |dup| {
+    ///             let warning = (dup.key().to_owned(), dup.first(), dup.second());
+    ///             warnings.lock().unwrap().push(warning);
+    ///         }
+    ///     });
+    ///
+    /// let toml = "name = \"old\"\nname = \"new\"\n";
+    /// let _: Value = options.from_str(toml).unwrap();
+    /// assert_eq!(*warnings.lock().unwrap(), [("name".to_owned(), 0..4, 13..17)]);
+    /// ```
+    #[must_use]
+    pub fn on_duplicate_key<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&DuplicateKey) + Send + Sync + 'static,
+    {
+        self.on_duplicate_key = Some(DuplicateKeyCallback(Arc::new(f)));
+        self
+    }
+
+    /// Sets how `None` values are serialized.
+    #[must_use]
+    pub fn null_policy(mut self, nulls: NullPolicy) -> Self {
+        self.nulls = Some(nulls);
+        self
+    }
+
+    /// Deserializes a string into a type using these settings.
+    ///
+    /// See [`from_str`](crate::from_str).
+    pub fn from_str<'de, T>(&self, s: &'de str) -> Result<T, crate::Error>
+    where
+        T: de::Deserialize<'de>,
+    {
+        crate::de::from_str_with_options(s, self)
+    }
+
+    /// Deserializes a byte slice into a type using these settings.
+    ///
+    /// See [`from_slice`](crate::from_slice).
+    pub fn from_slice<'de, T>(&self, bytes: &'de [u8]) -> Result<T, crate::Error>
+    where
+        T: de::Deserialize<'de>,
+    {
+        crate::de::from_slice_with_options(bytes, self)
+    }
+
+    /// Deserializes a string into an existing value using these settings.
+    ///
+    /// See [`from_str_into`](crate::from_str_into).
+    pub fn from_str_into<'de, T>(&self, s: &'de str, place: &mut T) -> Result<(), crate::Error>
+    where
+        T: ser::Serialize + de::Deserialize<'de>,
+    {
+        crate::de::from_str_into_with_options(s, place, self)
+    }
+
+    /// Deserializes a string into a type using these settings, with overrides
+    /// taken from environment variables.
+    ///
+    /// See [`from_str_with_env`](crate::from_str_with_env).
+    pub fn from_str_with_env<'de, T>(
+        &self,
+        s: &'de str,
+        prefix: &str,
+        separator: &str,
+    ) -> Result<T, crate::Error>
+    where
+        T: de::Deserialize<'de>,
+    {
+        crate::de::from_str_with_env_with_options(s, prefix, separator, self)
+    }
+
+    /// Deserializes the TOML front matter at the start of a file using these
+    /// settings.
+    ///
+    /// See [`from_front_matter`](crate::from_front_matter).
+    pub fn from_front_matter<'de, T>(&self, s: &'de str) -> Result<Option<T>, crate::Error>
+    where
+        T: de::Deserialize<'de>,
+    {
+        crate::de::from_front_matter_with_options(s, self)
+    }
+
+    /// Deserializes a single TOML value using these settings.
+    ///
+    /// See [`from_value_str`](crate::from_value_str).
+    pub fn from_value_str<'de, T>(&self, s: &'de str) -> Result<T, crate::Error>
+    where
+        T: de::Deserialize<'de>,
+    {
+        crate::de::from_value_str_with_options(s, self)
+    }
+
+    /// Deserializes the value captured by a [`RawValue`] using these settings.
+    ///
+    /// See [`RawValue::deserialize_into`]. Settings that affect parsing, such
+    /// as the version, were already applied when the document containing the
+    /// value was parsed.
+    pub fn deserialize_raw<T>(&self, raw: &RawValue) -> Result<T, crate::Error>
+    where
+        T: de::DeserializeOwned,
+    {
+        raw.deserialize_with_options(self)
+    }
+
+    /// Serializes a value as a string of TOML using these settings.
+    ///
+    /// See [`to_string`](crate::to_string).
+    pub fn to_string<T>(&self, value: &T) -> Result<String, crate::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        crate::ser::to_string_with_options(value, self)
+    }
+
+    /// Serializes a value as a string of TOML placed at the given key path
+    /// using these settings.
+    ///
+    /// See [`to_string_at`](crate::to_string_at).
+    pub fn to_string_at<T>(&self, value: &T, path: &[&str]) -> Result<String, crate::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        crate::ser::to_string_at_with_options(value, path, self)
+    }
+
+    /// Serializes a value as one entry of an array of tables at the given key
+    /// path using these settings.
+    ///
+    /// See [`to_string_array_entry_at`](crate::to_string_array_entry_at).
+    pub fn to_string_array_entry_at<T>(
+        &self,
+        value: &T,
+        path: &[&str],
+    ) -> Result<String, crate::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        crate::ser::to_string_array_entry_at_with_options(value, path, self)
+    }
+
+    /// Serializes a value as a string of TOML in canonical form using these
+    /// settings.
+    ///
+    /// See [`to_string_canonical`](crate::to_string_canonical).
+    pub fn to_string_canonical<T>(&self, value: &T) -> Result<String, crate::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        crate::ser::to_string_canonical_with_options(value, self)
+    }
+
+    /// Serializes a value as a single TOML value using these settings.
+    ///
+    /// See [`to_value_string`](crate::to_value_string).
+    pub fn to_value_string<T>(&self, value: &T) -> Result<String, crate::Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        crate::ser::to_value_string_with_options(value, self)
+    }
+}
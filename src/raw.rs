@@ -0,0 +1,458 @@
+use crate::de::{Source, TablePair, Value, E};
+use crate::options::Options;
+use crate::tokens::Span;
+use serde::de::{
+    self, Deserialize, DeserializeOwned, DeserializeSeed, Deserializer, IntoDeserializer,
+    SeqAccess, Visitor,
+};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::borrow::Cow;
+use std::fmt;
+use std::vec;
+
+pub(crate) const TOKEN: &str = "$__basic_toml_private_RawValue";
+
+// Like `TOKEN`, but without keeping the text of the document, for capturing
+// the parts of a table.
+pub(crate) const VALUE_TOKEN: &str = "$__basic_toml_private_RawValue_value";
+
+/// A value or table from a TOML document, kept for deserializing later.
+///
+/// Deserializing a `RawValue` captures the value as it was written in the
+/// document, without deciding on a type for it. It can then be turned into
+/// whatever type turns out to be appropriate with
+/// [`deserialize_into`](RawValue::deserialize_into). This is useful when the
+/// type isn't known until part of the document has been looked at, as in a
+/// plugin system where each plugin has its own section of a shared config file.
+///
+/// Errors from `deserialize_into` carry the line and column in the original
+/// document, or name the environment variable that the value came from when it
+/// was deserialized with [`from_str_with_env`](crate::from_str_with_env).
+///
+/// ```
+/// use basic_toml::RawValue;
+/// use serde::Deserialize;
+/// use std::collections::BTreeMap;
+///
+/// #[derive(Deserialize)]
+/// struct Config {
+///     plugins: BTreeMap<String, RawValue>,
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct Greeter {
+///     greeting: String,
+/// }
+///
+/// let toml = r#"
+///     [plugins.greeter]
+///     greeting = "hello"
+///
+///     [plugins.counter]
+///     start = "one"
+/// "#;
+///
+/// let config: Config = basic_toml::from_str(toml).unwrap();
+///
+/// let greeter: Greeter = config.plugins["greeter"].deserialize_into().unwrap();
+/// assert_eq!(greeter.greeting, "hello");
+///
+/// let err = config.plugins["counter"].deserialize_into::<u32>().unwrap_err();
+/// assert_eq!(err.line_col(), Some((4, 4)));
+/// ```
+///
+/// A `RawValue` can only be deserialized from TOML by this crate, and not from
+/// the top level of a document. Serializing a `RawValue` writes out the value
+/// that it holds.
+#[derive(Clone, Debug)]
+pub struct RawValue {
+    value: Value<'static>,
+    excerpt: Option<Excerpt>,
+    // The environment variables which parts of `value` were taken from. Their
+    // spans are at `len + 1 + i` for the i'th variable, past the end of the
+    // input of length `len`.
+    env: Vec<String>,
+    len: usize,
+}
+
+// The lines of the document which a `RawValue` was taken from, for locating
+// errors. `text` begins at offset `start` of the input, on line `line` of the
+// file.
+#[derive(Clone, Debug)]
+struct Excerpt {
+    text: String,
+    start: usize,
+    line: usize,
+}
+
+impl RawValue {
+    /// Deserializes the captured value into `T`.
+    ///
+    /// The value was parsed along with the rest of the document, so only the
+    /// settings which apply while deserializing, such as
+    /// [`Options::coerce`](crate::Options::coerce), can still make a
+    /// difference. See [`Options::deserialize_raw`](crate::Options::deserialize_raw)
+    /// to pass them.
+    pub fn deserialize_into<T>(&self) -> Result<T, crate::Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.deserialize_with_options(&Options::default())
+    }
+
+    pub(crate) fn deserialize_with_options<T>(&self, options: &Options) -> Result<T, crate::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let linecol = self.excerpt.as_ref().map(|excerpt| {
+            move |at: usize| {
+                let at = at.saturating_sub(excerpt.start).min(excerpt.text.len());
+                let (line, col) = crate::de::to_linecol(&excerpt.text, at);
+                (excerpt.line + line, col)
+            }
+        });
+        let linecol = linecol
+            .as_ref()
+            .map(|f| f as &dyn Fn(usize) -> (usize, usize));
+        let env = |at: usize| {
+            let i = at.checked_sub(self.len + 1)?;
+            self.env.get(i).cloned()
+        };
+        crate::de::from_raw_value(self.value.clone(), &env, linecol, options)
+    }
+
+    pub(crate) fn into_value(self) -> Value<'static> {
+        self.value
+    }
+}
+
+// Hands a value over from the deserializer to `RawValueVisitor`, when asked
+// for `TOKEN` or `VALUE_TOKEN`. The value is spelled out as a sequence of
+// primitives that includes the positions of all of its parts, so that it makes
+// it through the serde data model intact.
+pub(crate) struct RawAccess {
+    elements: vec::IntoIter<Element>,
+}
+
+enum Element {
+    Bool(bool),
+    I64(i64),
+    F64(f64),
+    Usize(usize),
+    String(String),
+}
+
+// The kinds of value in the sequence, each followed by its contents.
+const INTEGER: usize = 0;
+const FLOAT: usize = 1;
+const BOOLEAN: usize = 2;
+const STRING: usize = 3;
+const ARRAY: usize = 4;
+const INLINE_TABLE: usize = 5;
+const DOTTED_TABLE: usize = 6;
+#[cfg(feature = "arbitrary_precision")]
+const NUMBER: usize = 7;
+
+impl RawAccess {
+    pub(crate) fn new(value: Value<'static>, source: Option<Source>) -> Self {
+        let mut elements = vec![Element::String(TOKEN.to_owned())];
+        match source.as_ref().and_then(|source| excerpt(&value, source)) {
+            Some(excerpt) => elements.extend([
+                Element::Bool(true),
+                Element::String(excerpt.text),
+                Element::Usize(excerpt.start),
+                Element::Usize(excerpt.line),
+            ]),
+            None => elements.push(Element::Bool(false)),
+        }
+        let (len, env) = match source {
+            Some(source) => (source.len, source.env.to_vec()),
+            None => (0, Vec::new()),
+        };
+        elements.push(Element::Usize(len));
+        elements.push(Element::Usize(env.len()));
+        elements.extend(env.into_iter().map(Element::String));
+        flatten(value, &mut elements);
+        RawAccess {
+            elements: elements.into_iter(),
+        }
+    }
+}
+
+fn flatten(value: Value<'static>, elements: &mut Vec<Element>) {
+    elements.push(Element::Usize(value.start));
+    elements.push(Element::Usize(value.end));
+    flatten_e(value.e, elements);
+}
+
+fn flatten_e(e: E<'static>, elements: &mut Vec<Element>) {
+    match e {
+        E::Integer(i) => elements.extend([Element::Usize(INTEGER), Element::I64(i)]),
+        E::Float(f) => elements.extend([Element::Usize(FLOAT), Element::F64(f)]),
+        E::Boolean(b) => elements.extend([Element::Usize(BOOLEAN), Element::Bool(b)]),
+        E::String(s) => {
+            let s = Element::String(s.into_owned());
+            elements.extend([Element::Usize(STRING), s]);
+        }
+        E::Array(values) => {
+            elements.extend([Element::Usize(ARRAY), Element::Usize(values.len())]);
+            for value in values {
+                flatten(value, elements);
+            }
+        }
+        E::InlineTable(values) => flatten_table(INLINE_TABLE, values, elements),
+        E::DottedTable(values) => flatten_table(DOTTED_TABLE, values, elements),
+        #[cfg(feature = "arbitrary_precision")]
+        E::Number(lexeme, e) => {
+            let lexeme = Element::String(lexeme.into_owned());
+            elements.extend([Element::Usize(NUMBER), lexeme, Element::Bool(e.is_some())]);
+            if let Some(e) = e {
+                flatten_e(*e, elements);
+            }
+        }
+    }
+}
+
+fn flatten_table(kind: usize, values: Vec<TablePair<'static>>, elements: &mut Vec<Element>) {
+    elements.extend([Element::Usize(kind), Element::Usize(values.len())]);
+    for ((span, key), value) in values {
+        elements.push(Element::Usize(span.start));
+        elements.push(Element::Usize(span.end));
+        elements.push(Element::String(key.into_owned()));
+        flatten(value, elements);
+    }
+}
+
+impl<'de> SeqAccess<'de> for RawAccess {
+    type Error = Box<crate::de::Error>;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let element = match self.elements.next() {
+            Some(element) => element,
+            None => return Ok(None),
+        };
+        match element {
+            Element::Bool(b) => seed.deserialize(b.into_deserializer()),
+            Element::I64(i) => seed.deserialize(i.into_deserializer()),
+            Element::F64(f) => seed.deserialize(f.into_deserializer()),
+            Element::Usize(n) => seed.deserialize(n.into_deserializer()),
+            Element::String(s) => seed.deserialize(s.into_deserializer()),
+        }
+        .map(Some)
+    }
+}
+
+// Copies the lines of the file that hold `value`.
+fn excerpt(value: &Value, source: &Source) -> Option<Excerpt> {
+    let mut range = None;
+    span(value, source.len, &mut range);
+    let (start, end) = range?;
+    let start = source.offset + start;
+    let end = source.offset + end;
+    let start = source.file[..start].rfind('\n').map_or(0, |i| i + 1);
+    Some(Excerpt {
+        text: source.file[start..end].to_owned(),
+        start: start - source.offset,
+        line: source.file[..start].matches('\n').count(),
+    })
+}
+
+// Widens `range` to cover every part of `value` which is inside the input,
+// leaving out values taken from environment variables.
+fn span(value: &Value, len: usize, range: &mut Option<(usize, usize)>) {
+    if value.end <= len {
+        *range = Some(match *range {
+            Some((start, end)) => (start.min(value.start), end.max(value.end)),
+            None => (value.start, value.end),
+        });
+    }
+    match &value.e {
+        E::Array(values) => {
+            for value in values {
+                span(value, len, range);
+            }
+        }
+        E::InlineTable(values) | E::DottedTable(values) => {
+            for (_, value) in values {
+                span(value, len, range);
+            }
+        }
+        _ => {}
+    }
+}
+
+pub(crate) fn deserialize<'de, D>(
+    deserializer: D,
+    token: &'static str,
+) -> Result<RawValue, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_newtype_struct(token, RawValueVisitor)
+}
+
+impl<'de> Deserialize<'de> for RawValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserialize(deserializer, TOKEN)
+    }
+}
+
+struct RawValueVisitor;
+
+impl<'de> Visitor<'de> for RawValueVisitor {
+    type Value = RawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a TOML value")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        match seq.next_element::<String>()? {
+            Some(ref token) if token == TOKEN => {}
+            _ => return Err(de::Error::invalid_type(de::Unexpected::Seq, &self)),
+        }
+        let excerpt = if next::<bool, _>(&mut seq)? {
+            Some(Excerpt {
+                text: next(&mut seq)?,
+                start: next(&mut seq)?,
+                line: next(&mut seq)?,
+            })
+        } else {
+            None
+        };
+        let len = next(&mut seq)?;
+        let env = (0..next(&mut seq)?)
+            .map(|_| next(&mut seq))
+            .collect::<Result<_, _>>()?;
+        let value = unflatten(&mut seq)?;
+        Ok(RawValue {
+            value,
+            excerpt,
+            env,
+            len,
+        })
+    }
+}
+
+fn next<'de, T, A>(seq: &mut A) -> Result<T, A::Error>
+where
+    T: Deserialize<'de>,
+    A: SeqAccess<'de>,
+{
+    match seq.next_element()? {
+        Some(element) => Ok(element),
+        None => Err(de::Error::custom("unexpected end of raw value")),
+    }
+}
+
+fn unflatten<'de, A>(seq: &mut A) -> Result<Value<'static>, A::Error>
+where
+    A: SeqAccess<'de>,
+{
+    let start = next(seq)?;
+    let end = next(seq)?;
+    let e = unflatten_e(seq)?;
+    Ok(Value { e, start, end })
+}
+
+fn unflatten_e<'de, A>(seq: &mut A) -> Result<E<'static>, A::Error>
+where
+    A: SeqAccess<'de>,
+{
+    let e = match next(seq)? {
+        INTEGER => E::Integer(next(seq)?),
+        FLOAT => E::Float(next(seq)?),
+        BOOLEAN => E::Boolean(next(seq)?),
+        STRING => E::String(Cow::Owned(next(seq)?)),
+        ARRAY => E::Array(
+            (0..next(seq)?)
+                .map(|_| unflatten(seq))
+                .collect::<Result<_, _>>()?,
+        ),
+        kind @ (INLINE_TABLE | DOTTED_TABLE) => {
+            let mut values = Vec::new();
+            for _ in 0..next(seq)? {
+                let span = Span {
+                    start: next(seq)?,
+                    end: next(seq)?,
+                };
+                let key = Cow::Owned(next(seq)?);
+                values.push(((span, key), unflatten(seq)?));
+            }
+            if kind == INLINE_TABLE {
+                E::InlineTable(values)
+            } else {
+                E::DottedTable(values)
+            }
+        }
+        #[cfg(feature = "arbitrary_precision")]
+        NUMBER => {
+            let lexeme = Cow::Owned(next(seq)?);
+            let e = if next(seq)? {
+                Some(Box::new(unflatten_e(seq)?))
+            } else {
+                None
+            };
+            E::Number(lexeme, e)
+        }
+        kind => {
+            return Err(de::Error::custom(format!(
+                "unknown raw value kind {}",
+                kind
+            )))
+        }
+    };
+    Ok(e)
+}
+
+impl Serialize for RawValue {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SerializeValue(&self.value.e).serialize(serializer)
+    }
+}
+
+struct SerializeValue<'a>(&'a E<'static>);
+
+impl<'a> Serialize for SerializeValue<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.0 {
+            E::Integer(i) => serializer.serialize_i64(*i),
+            E::Float(f) => serializer.serialize_f64(*f),
+            E::Boolean(b) => serializer.serialize_bool(*b),
+            E::String(s) => serializer.serialize_str(s),
+            E::Array(values) => {
+                let mut seq = serializer.serialize_seq(Some(values.len()))?;
+                for value in values {
+                    seq.serialize_element(&SerializeValue(&value.e))?;
+                }
+                seq.end()
+            }
+            E::InlineTable(values) | E::DottedTable(values) => {
+                let mut map = serializer.serialize_map(Some(values.len()))?;
+                for ((_, key), value) in values {
+                    map.serialize_entry(key, &SerializeValue(&value.e))?;
+                }
+                map.end()
+            }
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(lexeme, _) => {
+                serializer.serialize_newtype_struct(crate::number::TOKEN, lexeme)
+            }
+        }
+    }
+}
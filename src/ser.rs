@@ -1,5 +1,10 @@
+use crate::de::{TablePair, Value, E};
+use crate::options::{NullPolicy, Options};
+use crate::style;
+use crate::tokens::Span;
 use serde::ser::{self, Serialize};
-use std::cell::Cell;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
 use std::error;
 use std::fmt::{self, Display, Write};
 
@@ -12,11 +17,255 @@ pub fn to_string<T>(value: &T) -> Result<String, crate::Error>
 where
     T: ?Sized + Serialize,
 {
+    to_string_with_options(value, &Options::default())
+}
+
+pub(crate) fn to_string_with_options<T>(
+    value: &T,
+    options: &Options,
+) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut dst = String::with_capacity(128);
+    let nulls = options.nulls.as_ref();
+    let node = value.serialize(NodeSerializer { nulls })?;
+    match Serializer::new(&mut dst, nulls).write(&node) {
+        Ok(()) => {}
+        Err(Error::UnsupportedNone) if nulls == Some(&NullPolicy::Skip) => dst.clear(),
+        Err(e) => return Err(crate::Error::from(e)),
+    }
+    Ok(dst)
+}
+
+/// Serialize the given data structure as a String of TOML, placed at the given
+/// key path.
+///
+/// This is for writing a part of a larger document, such as a tool's section
+/// of a Cargo manifest. With a path of `["package", "metadata", "ourtool"]`, a
+/// struct is written under `[package.metadata.ourtool]` and the headers of any
+/// tables nested inside of it carry the same prefix, as in
+/// `[package.metadata.ourtool.nested]`. A value that is not a table is written
+/// as `ourtool = ...` under a `[package.metadata]` header.
+///
+/// An empty path produces the same output as [`to_string`].
+pub fn to_string_at<T>(value: &T, path: &[&str]) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_at_with_options(value, path, &Options::default())
+}
+
+pub(crate) fn to_string_at_with_options<T>(
+    value: &T,
+    path: &[&str],
+    options: &Options,
+) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    let mut dst = String::with_capacity(128);
+    let nulls = options.nulls.as_ref();
+    let node = value.serialize(NodeSerializer { nulls })?;
+    match serialize_at(&mut dst, &State::End, path, &node, false, nulls) {
+        Ok(()) => {}
+        Err(Error::UnsupportedNone) if nulls == Some(&NullPolicy::Skip) => dst.clear(),
+        Err(e) => return Err(crate::Error::from(e)),
+    }
+    Ok(dst)
+}
+
+/// Serialize the given data structure as one entry of an array of tables at
+/// the given key path.
+///
+/// The value needs to be a table, and is written under a `[[header]]` named by
+/// the full path, like `[[profile]]` or `[[package.metadata.ourtool.run]]`.
+/// This can be appended to an existing document to add another entry to the
+/// array.
+pub fn to_string_array_entry_at<T>(value: &T, path: &[&str]) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_array_entry_at_with_options(value, path, &Options::default())
+}
+
+pub(crate) fn to_string_array_entry_at_with_options<T>(
+    value: &T,
+    path: &[&str],
+    options: &Options,
+) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    if path.is_empty() {
+        let msg = "array of tables entry requires a non-empty key path";
+        return Err(crate::Error::from(<Error as ser::Error>::custom(msg)));
+    }
+    let nulls = options.nulls.as_ref();
+    let node = value.serialize(NodeSerializer { nulls })?;
+    if !matches!(node.shape(), Shape::Table) {
+        return Err(crate::Error::from(Error::UnsupportedType));
+    }
+    let mut dst = String::with_capacity(128);
+    serialize_at(&mut dst, &State::End, path, &node, true, nulls)?;
+    Ok(dst)
+}
+
+// Writes `node` with a chain of table states for the keys of `path` above it,
+// so that every header it writes starts with the full path.
+fn serialize_at(
+    dst: &mut String,
+    parent: &State,
+    path: &[&str],
+    node: &Node,
+    array: bool,
+    nulls: Option<&NullPolicy>,
+) -> Result<(), Error> {
+    let first = Cell::new(true);
+    let comment = RefCell::new(None);
+    let (key, rest) = match path.split_first() {
+        Some(split) => split,
+        None if array => {
+            let type_ = Cell::new(None);
+            let state = State::Array {
+                parent,
+                first: &first,
+                type_: &type_,
+                comment: &comment,
+            };
+            return Serializer {
+                dst,
+                state,
+                nulls,
+                inline: false,
+            }
+            .write(node);
+        }
+        None => {
+            let mut ser = Serializer {
+                dst,
+                state: parent.clone(),
+                nulls,
+                inline: false,
+            };
+            if let State::End = parent {
+                return ser.write(node);
+            }
+            // Same as an entry of a table.
+            let res = match node.shape() {
+                Shape::Array => ser.emit_inline(node),
+                _ => ser.write(node),
+            };
+            return match res {
+                Err(Error::UnsupportedNone) => skip_none(nulls, true),
+                res => res,
+            };
+        }
+    };
+    let table_emitted = Cell::new(false);
+    let state = State::Table {
+        key,
+        parent,
+        first: &first,
+        table_emitted: &table_emitted,
+        comment: &comment,
+    };
+    serialize_at(dst, &state, rest, node, array, nulls)
+}
+
+/// Serialize the given data structure as a String of TOML in canonical form.
+///
+/// Semantically equal values always produce byte-identical output, regardless
+/// of the order in which maps yield their entries or whether a table came from
+/// a struct or a map. Keys are sorted within each table, integers are written
+/// in decimal, strings and keys that are not bare are written as basic strings,
+/// tables are written with `[header]` sections and arrays of tables with
+/// `[[header]]` sections unless they are nested inside another array.
+///
+/// Serialization fails for the same kinds of values as [`to_string`], except
+/// that values are allowed to come after tables.
+pub fn to_string_canonical<T>(value: &T) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    to_string_canonical_with_options(value, &Options::default())
+}
+
+pub(crate) fn to_string_canonical_with_options<T>(
+    value: &T,
+    options: &Options,
+) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    let nulls = options.nulls.as_ref();
+    let values = match value.serialize(ValueSerializer {
+        nulls,
+        variants: false,
+    }) {
+        Ok(Value {
+            e: E::InlineTable(values),
+            ..
+        }) => values,
+        Ok(_) => return Err(crate::Error::from(Error::UnsupportedType)),
+        Err(Error::UnsupportedNone) if nulls == Some(&NullPolicy::Skip) => return Ok(String::new()),
+        Err(e) => return Err(crate::Error::from(e)),
+    };
     let mut dst = String::with_capacity(128);
-    value.serialize(&mut Serializer::new(&mut dst))?;
+    Canonical { dst: &mut dst }.table(&mut Vec::new(), values, false)?;
+    Ok(dst)
+}
+
+/// Serialize the given data structure as a single TOML value.
+///
+/// Unlike [`to_string`], which produces a whole document and so requires a
+/// table at the top level, this writes any value in the form it would take on
+/// the right-hand side of `key = ...`. Tables and arrays are written inline,
+/// like `{ version = "1", features = ["derive"] }` or `[1, 2, 3]`.
+///
+/// The result can be parsed back with [`from_value_str`](crate::from_value_str).
+pub fn to_value_string<T>(value: &T) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    to_value_string_with_options(value, &Options::default())
+}
+
+pub(crate) fn to_value_string_with_options<T>(
+    value: &T,
+    options: &Options,
+) -> Result<String, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    let nulls = options.nulls.as_ref();
+    let node = value.serialize(NodeSerializer { nulls })?;
+    let mut dst = String::with_capacity(32);
+    Serializer {
+        dst: &mut dst,
+        state: State::End,
+        nulls,
+        inline: true,
+    }
+    .write(&node)?;
     Ok(dst)
 }
 
+// Serializes the given data structure into the tree of values that the
+// deserializer builds while parsing a document, for `from_str_into` to lay the
+// document over. Newtype and struct variants are written as tables with a
+// single key, which is how the deserializer reads them back.
+pub(crate) fn to_value<T>(value: &T) -> Result<Value<'static>, crate::Error>
+where
+    T: ?Sized + Serialize,
+{
+    let ser = ValueSerializer {
+        nulls: None,
+        variants: true,
+    };
+    Ok(value.serialize(ser)?)
+}
+
 #[derive(Debug)]
 pub(crate) enum Error {
     /// Indicates that a Rust type was requested to be serialized but it was not
@@ -38,6 +287,10 @@ pub(crate) enum Error {
     /// None was attempted to be serialized, but it's not supported.
     UnsupportedNone,
 
+    /// A value wrapped in `Section` was found somewhere that a table header
+    /// cannot be written, such as inside an inline table or array.
+    SectionNotAllowed,
+
     /// A custom error which could be generated when serializing a particular
     /// type.
     Custom(String),
@@ -46,6 +299,10 @@ pub(crate) enum Error {
 struct Serializer<'a> {
     dst: &'a mut String,
     state: State<'a>,
+    nulls: Option<&'a NullPolicy>,
+    // Whether tables are written inline, `{ a = 1, b = 2 }`, rather than
+    // under headers. Set for everything inside an inline array or table.
+    inline: bool,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -61,36 +318,221 @@ enum State<'a> {
         parent: &'a State<'a>,
         first: &'a Cell<bool>,
         table_emitted: &'a Cell<bool>,
+        comment: &'a RefCell<Option<String>>,
     },
     Array {
         parent: &'a State<'a>,
         first: &'a Cell<bool>,
         type_: &'a Cell<Option<ArrayState>>,
-        len: Option<usize>,
+        comment: &'a RefCell<Option<String>>,
+    },
+    // An entry of an inline table.
+    InlineTable {
+        key: &'a str,
+        first: &'a Cell<bool>,
     },
     End,
 }
 
-struct SerializeSeq<'a, 'b> {
-    ser: &'b mut Serializer<'a>,
-    first: Cell<bool>,
-    type_: Cell<Option<ArrayState>>,
-    len: Option<usize>,
-}
-
-struct SerializeTable<'a, 'b> {
-    ser: &'b mut Serializer<'a>,
-    key: String,
-    first: Cell<bool>,
-    table_emitted: Cell<bool>,
-}
-
 impl<'a> Serializer<'a> {
-    fn new(dst: &'a mut String) -> Serializer<'a> {
+    fn new(dst: &'a mut String, nulls: Option<&'a NullPolicy>) -> Serializer<'a> {
         Serializer {
             dst,
             state: State::End,
+            nulls,
+            inline: false,
+        }
+    }
+
+    fn write(&mut self, node: &Node) -> Result<(), Error> {
+        match *node {
+            Node::None => Err(Error::UnsupportedNone),
+            Node::Scalar(ref scalar) => {
+                self.emit_key(ArrayState::Started)?;
+                scalar.emit(self.dst)?;
+                if let State::Table { .. } = self.state {
+                    self.dst.push('\n');
+                }
+                Ok(())
+            }
+            // Written as an empty inline table, or as an empty document at the
+            // top level.
+            Node::Unit => {
+                if let (State::End, false) = (&self.state, self.inline) {
+                    return Ok(());
+                }
+                self.display("{}", ArrayState::Started)
+            }
+            Node::Array(ref nodes) => {
+                self.array_type(ArrayState::Started);
+                self.write_array(nodes, None)
+            }
+            Node::Variant(variant, ref nodes) => {
+                // Written as `{ Variant = [...] }`, or as `Variant = [...]` at
+                // the top level of the document.
+                self.emit_key(ArrayState::Started)?;
+                let close = if let (State::End, false) = (&self.state, self.inline) {
+                    "\n"
+                } else {
+                    self.dst.push_str("{ ");
+                    " }"
+                };
+                self.escape_key(variant)?;
+                self.dst.push_str(" = ");
+                self.write_array(nodes, Some(close))
+            }
+            Node::Table(ref entries) => self.write_table(entries),
+            Node::Styled(name, ref node) => self.write_styled(name, node),
+            Node::Commented(ref comment, ref node) => {
+                self.add_comment(comment)?;
+                self.write(node)
+            }
+        }
+    }
+
+    // Writes the elements of an array. For the array of a tuple variant, whose
+    // key has been written already, `close` is what follows the closing
+    // bracket.
+    fn write_array(&mut self, nodes: &[Node], close: Option<&'static str>) -> Result<(), Error> {
+        let first = Cell::new(true);
+        let type_ = Cell::new(None);
+        for node in nodes {
+            let comment = RefCell::new(None);
+            let parent = match close {
+                Some(_) => &State::End,
+                None => &self.state,
+            };
+            let res = Serializer {
+                dst: &mut *self.dst,
+                state: State::Array {
+                    parent,
+                    first: &first,
+                    type_: &type_,
+                    comment: &comment,
+                },
+                nulls: self.nulls,
+                inline: self.inline || close.is_some(),
+            }
+            .write(node);
+            match res {
+                Ok(()) => first.set(false),
+                Err(Error::UnsupportedNone) => skip_none(self.nulls, false)?,
+                Err(e) => return Err(e),
+            }
+        }
+        match type_.get() {
+            Some(ArrayState::StartedAsATable) => return Ok(()),
+            Some(ArrayState::Started) => self.dst.push(']'),
+            None => {
+                assert!(first.get());
+                if close.is_none() {
+                    self.emit_key(ArrayState::Started)?;
+                }
+                self.dst.push_str("[]");
+            }
+        }
+        if let Some(close) = close {
+            self.dst.push_str(close);
+        }
+        if let State::Table { .. } = self.state {
+            self.dst.push('\n');
+        }
+        Ok(())
+    }
+
+    fn write_table(&mut self, entries: &[(String, Node)]) -> Result<(), Error> {
+        if self.inline {
+            self.emit_key(ArrayState::Started)?;
+            self.dst.push('{');
+        } else {
+            self.array_type(ArrayState::StartedAsATable);
+        }
+        let first = Cell::new(true);
+        let table_emitted = Cell::new(false);
+        for (key, node) in entries {
+            let comment = RefCell::new(None);
+            let state = if self.inline {
+                State::InlineTable { key, first: &first }
+            } else {
+                State::Table {
+                    key,
+                    parent: &self.state,
+                    first: &first,
+                    table_emitted: &table_emitted,
+                    comment: &comment,
+                }
+            };
+            let mut ser = Serializer {
+                dst: &mut *self.dst,
+                state,
+                nulls: self.nulls,
+                inline: self.inline,
+            };
+            // Arrays which are not made up of only tables can't be written as
+            // an array of tables, so any tables inside them have to be inline
+            // tables.
+            let res = if !ser.inline && matches!(node.shape(), Shape::Array) {
+                ser.emit_inline(node)
+            } else {
+                ser.write(node)
+            };
+            match res {
+                Ok(()) => first.set(false),
+                Err(Error::UnsupportedNone) => skip_none(self.nulls, true)?,
+                Err(e) => return Err(e),
+            }
+        }
+        if self.inline {
+            self.dst.push_str(if first.get() { "}" } else { " }" });
+            if let State::Table { .. } = self.state {
+                self.dst.push('\n');
+            }
+        } else if first.get() {
+            let state = self.state.clone();
+            self.emit_table_header(&state)?;
+        }
+        Ok(())
+    }
+
+    // Writes a value wrapped in `Inline`, `Dotted` or `Section`.
+    fn write_styled(&mut self, name: &str, node: &Node) -> Result<(), Error> {
+        if self.inline {
+            return match name {
+                style::SECTION => Err(Error::SectionNotAllowed),
+                _ => self.write(node),
+            };
+        }
+        match (name, &self.state) {
+            (style::INLINE, State::Table { .. } | State::Array { .. }) => self.emit_inline(node),
+            (style::DOTTED, State::Table { .. }) => self.emit_dotted(&mut Vec::new(), node),
+            _ => self.write(node),
+        }
+    }
+
+    // Attaches a comment to the current table entry or array element, to be
+    // written above it.
+    fn add_comment(&mut self, comment: &str) -> Result<(), Error> {
+        match self.state {
+            State::Table { comment: cell, .. } | State::Array { comment: cell, .. } => {
+                // Nested `Commented` wrappers add up.
+                match *cell.borrow_mut() {
+                    Some(ref mut pending) => {
+                        pending.push('\n');
+                        pending.push_str(comment);
+                    }
+                    ref mut pending @ None => *pending = Some(comment.to_owned()),
+                }
+            }
+            State::End if !self.inline => {
+                // A comment on the whole document, kept apart from the first
+                // key or table with a blank line.
+                emit_comment(self.dst, comment)?;
+                self.dst.push('\n');
+            }
+            // Inline tables have nowhere to put comments.
+            State::InlineTable { .. } | State::End => {}
         }
+        Ok(())
     }
 
     fn display<T: Display>(&mut self, t: T, type_: ArrayState) -> Result<(), Error> {
@@ -102,27 +544,91 @@ impl<'a> Serializer<'a> {
         Ok(())
     }
 
+    fn emit_inline(&mut self, node: &Node) -> Result<(), Error> {
+        Serializer {
+            dst: &mut *self.dst,
+            state: self.state.clone(),
+            nulls: self.nulls,
+            inline: true,
+        }
+        .write(node)
+    }
+
+    // Writes the tables in `node` as dotted keys below the current key, and
+    // everything else inline.
+    fn emit_dotted<'n>(&mut self, path: &mut Vec<&'n str>, node: &'n Node) -> Result<(), Error> {
+        match *node {
+            Node::Table(ref entries) => {
+                let mut empty = true;
+                for (key, node) in entries {
+                    path.push(key);
+                    let res = self.emit_dotted(path, node);
+                    path.pop();
+                    match res {
+                        Ok(()) => empty = false,
+                        Err(Error::UnsupportedNone) => skip_none(self.nulls, true)?,
+                        Err(e) => return Err(e),
+                    }
+                }
+                // A table without any values still needs to show up in the
+                // output.
+                if empty {
+                    self.emit_dotted_key(path)?;
+                    self.dst.push_str("{}\n");
+                }
+                Ok(())
+            }
+            Node::Styled(style::SECTION, _) => Err(Error::SectionNotAllowed),
+            Node::Styled(style::DOTTED, ref node) | Node::Commented(_, ref node) => {
+                self.emit_dotted(path, node)
+            }
+            _ => {
+                // Written before emitting the key, so that nothing is written
+                // for a `None` that gets skipped.
+                let mut inline = String::new();
+                Serializer {
+                    dst: &mut inline,
+                    state: State::End,
+                    nulls: self.nulls,
+                    inline: true,
+                }
+                .write(node)?;
+                self.emit_dotted_key(path)?;
+                self.dst.push_str(&inline);
+                self.dst.push('\n');
+                Ok(())
+            }
+        }
+    }
+
     fn emit_key(&mut self, type_: ArrayState) -> Result<(), Error> {
         self.array_type(type_);
         let state = self.state.clone();
-        self.do_emit_key(&state)
+        self.do_emit_key(&state, &[])
+    }
+
+    // Emits the key of the current table entry followed by `path`, as in
+    // `key.path.to.value = `.
+    fn emit_dotted_key(&mut self, path: &[&str]) -> Result<(), Error> {
+        let state = self.state.clone();
+        self.do_emit_key(&state, path)
     }
 
     // recursive implementation of `emit_key` above
-    fn do_emit_key(&mut self, state: &State) -> Result<(), Error> {
+    fn do_emit_key(&mut self, state: &State, path: &[&str]) -> Result<(), Error> {
         match *state {
             State::End => Ok(()),
             State::Array {
                 parent,
                 first,
                 type_,
-                len,
+                ..
             } => {
                 assert!(type_.get().is_some());
                 if first.get() {
-                    self.do_emit_key(parent)?;
+                    self.do_emit_key(parent, &[])?;
                 }
-                self.emit_array(first, len);
+                self.emit_array(first);
                 Ok(())
             }
             State::Table {
@@ -130,6 +636,7 @@ impl<'a> Serializer<'a> {
                 first,
                 table_emitted,
                 key,
+                ..
             } => {
                 if table_emitted.get() {
                     return Err(Error::ValueAfterTable);
@@ -138,6 +645,17 @@ impl<'a> Serializer<'a> {
                     self.emit_table_header(parent)?;
                     first.set(false);
                 }
+                self.emit_comments(state)?;
+                self.escape_key(key)?;
+                for key in path {
+                    self.dst.push('.');
+                    self.escape_key(key)?;
+                }
+                self.dst.push_str(" = ");
+                Ok(())
+            }
+            State::InlineTable { key, first } => {
+                self.dst.push_str(if first.get() { " " } else { ", " });
                 self.escape_key(key)?;
                 self.dst.push_str(" = ");
                 Ok(())
@@ -145,7 +663,7 @@ impl<'a> Serializer<'a> {
         }
     }
 
-    fn emit_array(&mut self, first: &Cell<bool>, _len: Option<usize>) {
+    fn emit_array(&mut self, first: &Cell<bool>) {
         if first.get() {
             self.dst.push('[');
         } else {
@@ -164,43 +682,12 @@ impl<'a> Serializer<'a> {
     }
 
     fn escape_key(&mut self, key: &str) -> Result<(), Error> {
-        let ok = !key.is_empty()
-            && key.chars().all(|c| match c {
-                'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => true,
-                _ => false,
-            });
-        if ok {
-            write!(self.dst, "{}", key).map_err(ser::Error::custom)?;
-        } else {
-            self.emit_str(key)?;
-        }
-        Ok(())
-    }
-
-    fn emit_str(&mut self, value: &str) -> Result<(), Error> {
-        self.dst.push('"');
-        for ch in value.chars() {
-            match ch {
-                '\u{8}' => self.dst.push_str("\\b"),
-                '\u{9}' => self.dst.push_str("\\t"),
-                '\u{a}' => self.dst.push_str("\\n"),
-                '\u{c}' => self.dst.push_str("\\f"),
-                '\u{d}' => self.dst.push_str("\\r"),
-                '\u{22}' => self.dst.push_str("\\\""),
-                '\u{5c}' => self.dst.push_str("\\\\"),
-                c if c <= '\u{1f}' || c == '\u{7f}' => {
-                    write!(self.dst, "\\u{:04X}", ch as u32).map_err(ser::Error::custom)?;
-                }
-                ch => self.dst.push(ch),
-            }
-        }
-        self.dst.push('"');
-        Ok(())
+        escape_key(self.dst, key)
     }
 
     fn emit_table_header(&mut self, state: &State) -> Result<(), Error> {
         let array_of_tables = match *state {
-            State::End => return Ok(()),
+            State::InlineTable { .. } | State::End => return Ok(()),
             State::Array { .. } => true,
             State::Table { .. } => false,
         };
@@ -248,8 +735,9 @@ impl<'a> Serializer<'a> {
                     }
                 }
             }
-            State::End => {}
+            State::InlineTable { .. } | State::End => {}
         }
+        self.emit_comments(state)?;
         self.dst.push('[');
         if array_of_tables {
             self.dst.push('[');
@@ -262,10 +750,30 @@ impl<'a> Serializer<'a> {
         Ok(())
     }
 
+    // Writes the comments attached to `state` and to any of its ancestors
+    // which have not been written yet, such as a table whose header was left
+    // out because it only holds other tables.
+    fn emit_comments(&mut self, state: &State) -> Result<(), Error> {
+        let (parent, comment) = match *state {
+            State::Table {
+                parent, comment, ..
+            }
+            | State::Array {
+                parent, comment, ..
+            } => (parent, comment),
+            State::InlineTable { .. } | State::End => return Ok(()),
+        };
+        self.emit_comments(parent)?;
+        if let Some(comment) = comment.take() {
+            emit_comment(self.dst, &comment)?;
+        }
+        Ok(())
+    }
+
     fn emit_key_part(&mut self, key: &State) -> Result<bool, Error> {
         match *key {
             State::Array { parent, .. } => self.emit_key_part(parent),
-            State::End => Ok(true),
+            State::InlineTable { .. } | State::End => Ok(true),
             State::Table {
                 key,
                 parent,
@@ -284,120 +792,501 @@ impl<'a> Serializer<'a> {
     }
 }
 
-macro_rules! serialize_float {
-    ($this:expr, $v:expr) => {{
-        $this.emit_key(ArrayState::Started)?;
-        match ($v.is_sign_negative(), $v.is_nan(), $v == 0.0) {
-            (_, true, _) => write!($this.dst, "nan"),
-            (true, false, true) => write!($this.dst, "-0.0"),
-            (false, false, true) => write!($this.dst, "0.0"),
-            (_, false, false) => write!($this.dst, "{}", $v).and_then(|()| {
-                if $v % 1.0 == 0.0 {
-                    write!($this.dst, ".0")
-                } else {
-                    Ok(())
-                }
-            }),
-        }
-        .map_err(ser::Error::custom)?;
+// Decides whether a `None` inside an array (`table_entry: false`) or as the
+// value of a table entry (`table_entry: true`) is left out of the output.
+// Without a policy, `None` is left out of tables only. Otherwise the error is
+// turned into one which enclosing tables do not swallow, since part of the
+// value may have been written already.
+fn skip_none(nulls: Option<&NullPolicy>, table_entry: bool) -> Result<(), Error> {
+    let skip = match nulls {
+        None => table_entry,
+        Some(NullPolicy::Skip) => true,
+        Some(_) => false,
+    };
+    if skip {
+        Ok(())
+    } else {
+        Err(ser::Error::custom(Error::UnsupportedNone))
+    }
+}
 
-        if let State::Table { .. } = $this.state {
-            $this.dst.push_str("\n");
+fn emit_comment(dst: &mut String, comment: &str) -> Result<(), Error> {
+    for line in comment.lines() {
+        if let Some(ch) = line
+            .chars()
+            .find(|&c| c != '\t' && (c <= '\u{1f}' || c == '\u{7f}'))
+        {
+            return Err(ser::Error::custom(format!(
+                "comment cannot contain {:?}",
+                ch,
+            )));
         }
-        return Ok(());
-    }};
+        dst.push('#');
+        if !line.is_empty() {
+            dst.push(' ');
+            dst.push_str(line);
+        }
+        dst.push('\n');
+    }
+    Ok(())
 }
 
-impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
-    type Ok = ();
-    type Error = Error;
-    type SerializeSeq = SerializeSeq<'a, 'b>;
-    type SerializeTuple = SerializeSeq<'a, 'b>;
-    type SerializeTupleStruct = SerializeSeq<'a, 'b>;
-    type SerializeTupleVariant = ser::Impossible<(), Error>;
-    type SerializeMap = SerializeTable<'a, 'b>;
-    type SerializeStruct = SerializeTable<'a, 'b>;
-    type SerializeStructVariant = ser::Impossible<(), Error>;
-
-    fn serialize_bool(self, v: bool) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
+fn escape_key(dst: &mut String, key: &str) -> Result<(), Error> {
+    let ok = !key.is_empty()
+        && key.chars().all(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => true,
+            _ => false,
+        });
+    if ok {
+        write!(dst, "{}", key).map_err(ser::Error::custom)?;
+    } else {
+        emit_basic_str(dst, key)?;
     }
+    Ok(())
+}
 
-    fn serialize_i8(self, v: i8) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
-    }
+#[derive(Copy, Clone)]
+enum StrStyle {
+    Basic,
+    Literal,
+    MultilineBasic,
+    MultilineLiteral,
+}
 
-    fn serialize_i16(self, v: i16) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
+impl StrStyle {
+    fn from_name(name: &str) -> Option<StrStyle> {
+        match name {
+            style::BASIC => Some(StrStyle::Basic),
+            style::LITERAL => Some(StrStyle::Literal),
+            style::MULTILINE_BASIC => Some(StrStyle::MultilineBasic),
+            style::MULTILINE_LITERAL => Some(StrStyle::MultilineLiteral),
+            _ => None,
+        }
     }
 
-    fn serialize_i32(self, v: i32) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
+    // Literal strings are preferred for anything with backslashes in it, so
+    // that paths and regexes don't need escaping, and multi-line strings for
+    // anything with newlines in it.
+    fn pick(value: &str) -> StrStyle {
+        let multiline = value.contains('\n');
+        match (
+            value.contains('\\') && is_literal(value, multiline),
+            multiline,
+        ) {
+            (true, false) => StrStyle::Literal,
+            (true, true) => StrStyle::MultilineLiteral,
+            (false, false) => StrStyle::Basic,
+            (false, true) => StrStyle::MultilineBasic,
+        }
     }
+}
 
-    fn serialize_i64(self, v: i64) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
-    }
+// Whether `value` can be written as a literal string without any escapes.
+fn is_literal(value: &str, multiline: bool) -> bool {
+    let quotes = if multiline {
+        value.contains("'''") || value.ends_with('\'')
+    } else {
+        value.contains('\'')
+    };
+    !quotes
+        && value.chars().all(|c| match c {
+            '\t' => true,
+            '\n' => multiline,
+            c => c > '\u{1f}' && c != '\u{7f}',
+        })
+}
 
-    fn serialize_u8(self, v: u8) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
+fn emit_str(dst: &mut String, value: &str, style: StrStyle) -> Result<(), Error> {
+    match style {
+        StrStyle::Basic => emit_basic_str(dst, value),
+        StrStyle::MultilineBasic => emit_multiline_basic_str(dst, value),
+        StrStyle::Literal | StrStyle::MultilineLiteral => {
+            let multiline = matches!(style, StrStyle::MultilineLiteral);
+            if !is_literal(value, multiline) {
+                return Err(ser::Error::custom(format!(
+                    "string cannot be written as a {}literal string: {:?}",
+                    if multiline { "multi-line " } else { "" },
+                    value,
+                )));
+            }
+            // A newline straight after the opening delimiter is trimmed by
+            // the parser, so always writing one keeps any leading newline in
+            // the value intact.
+            let delim = if multiline { "'''\n" } else { "'" };
+            dst.push_str(delim);
+            dst.push_str(value);
+            dst.push_str(delim.trim_end());
+            Ok(())
+        }
     }
+}
 
-    fn serialize_u16(self, v: u16) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
+fn emit_multiline_basic_str(dst: &mut String, value: &str) -> Result<(), Error> {
+    dst.push_str("\"\"\"\n");
+    let mut chars = value.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\u{8}' => dst.push_str("\\b"),
+            '\u{c}' => dst.push_str("\\f"),
+            '\u{d}' => dst.push_str("\\r"),
+            '\u{5c}' => dst.push_str("\\\\"),
+            // Escape quotes which would otherwise run into each other or into
+            // the closing delimiter.
+            '\u{22}' if matches!(chars.peek(), Some('"') | None) => dst.push_str("\\\""),
+            c if c != '\t' && c != '\n' && (c <= '\u{1f}' || c == '\u{7f}') => {
+                write!(dst, "\\u{:04X}", ch as u32).map_err(ser::Error::custom)?;
+            }
+            ch => dst.push(ch),
+        }
     }
+    dst.push_str("\"\"\"");
+    Ok(())
+}
 
-    fn serialize_u32(self, v: u32) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
+fn emit_basic_str(dst: &mut String, value: &str) -> Result<(), Error> {
+    dst.push('"');
+    for ch in value.chars() {
+        match ch {
+            '\u{8}' => dst.push_str("\\b"),
+            '\u{9}' => dst.push_str("\\t"),
+            '\u{a}' => dst.push_str("\\n"),
+            '\u{c}' => dst.push_str("\\f"),
+            '\u{d}' => dst.push_str("\\r"),
+            '\u{22}' => dst.push_str("\\\""),
+            '\u{5c}' => dst.push_str("\\\\"),
+            c if c <= '\u{1f}' || c == '\u{7f}' => {
+                write!(dst, "\\u{:04X}", ch as u32).map_err(ser::Error::custom)?;
+            }
+            ch => dst.push(ch),
+        }
     }
+    dst.push('"');
+    Ok(())
+}
 
-    fn serialize_u64(self, v: u64) -> Result<(), Self::Error> {
-        self.display(v, ArrayState::Started)
-    }
+#[derive(Copy, Clone)]
+struct IntFormat {
+    radix: u32,
+    grouped: bool,
+}
 
-    fn serialize_f32(self, v: f32) -> Result<(), Self::Error> {
-        serialize_float!(self, v)
+impl IntFormat {
+    const DECIMAL: IntFormat = IntFormat {
+        radix: 10,
+        grouped: false,
+    };
+
+    fn from_name(name: &str) -> Option<IntFormat> {
+        let radix = match name {
+            style::HEX => 16,
+            style::OCTAL => 8,
+            style::BINARY => 2,
+            style::GROUPED => {
+                return Some(IntFormat {
+                    grouped: true,
+                    ..IntFormat::DECIMAL
+                })
+            }
+            _ => return None,
+        };
+        Some(IntFormat {
+            radix,
+            grouped: false,
+        })
     }
 
-    fn serialize_f64(self, v: f64) -> Result<(), Self::Error> {
-        serialize_float!(self, v)
+    // Combines this format with that of a style wrapped around it, so that for
+    // example `Hex(Grouped(n))` is written in grouped hexadecimal. The radix of
+    // the innermost style wins.
+    fn within(self, outer: IntFormat) -> IntFormat {
+        IntFormat {
+            radix: if self.radix == 10 {
+                outer.radix
+            } else {
+                self.radix
+            },
+            grouped: self.grouped || outer.grouped,
+        }
     }
+}
 
-    fn serialize_char(self, v: char) -> Result<(), Self::Error> {
-        let mut buf = [0; 4];
-        self.serialize_str(v.encode_utf8(&mut buf))
+fn emit_int(dst: &mut String, value: i128, format: IntFormat) -> Result<(), Error> {
+    let (prefix, group) = match format.radix {
+        16 => ("0x", 4),
+        8 => ("0o", 3),
+        2 => ("0b", 4),
+        _ => ("", 3),
+    };
+    if value < 0 {
+        if format.radix != 10 {
+            return Err(ser::Error::custom(format!(
+                "negative integer cannot be written with a {} prefix: {}",
+                prefix, value,
+            )));
+        }
+        dst.push('-');
+    }
+    // TOML integers are 64-bit signed, so larger values would not parse back.
+    if value > i128::from(i64::MAX) {
+        return Err(ser::Error::custom(format!(
+            "integer exceeds i64::MAX and cannot be written as a TOML integer: {}",
+            value,
+        )));
+    }
+    let magnitude = value.unsigned_abs();
+    let digits = match format.radix {
+        16 => format!("{:X}", magnitude),
+        8 => format!("{:o}", magnitude),
+        2 => format!("{:b}", magnitude),
+        _ => magnitude.to_string(),
+    };
+    dst.push_str(prefix);
+    for (i, digit) in digits.chars().enumerate() {
+        if format.grouped && i > 0 && (digits.len() - i) % group == 0 {
+            dst.push('_');
+        }
+        dst.push(digit);
     }
+    Ok(())
+}
 
-    fn serialize_str(self, value: &str) -> Result<(), Self::Error> {
-        self.emit_key(ArrayState::Started)?;
-        self.emit_str(value)?;
-        if let State::Table { .. } = self.state {
-            self.dst.push('\n');
+// Writes the shortest representation of a float which parses back to the same
+// value, using an exponent for very large and very small magnitudes.
+macro_rules! write_float {
+    ($dst:expr, $v:expr) => {{
+        let v = $v;
+        let scientific = format!("{:e}", v);
+        let exponent = match scientific.split_once('e') {
+            Some((_, exponent)) => exponent.parse().unwrap_or(0),
+            None => 0,
+        };
+        if v.is_nan() {
+            // The sign of a NaN is the only part of its payload that TOML can
+            // represent.
+            $dst.push_str(if v.is_sign_negative() { "-nan" } else { "nan" });
+            Ok(())
+        } else if v.is_finite() && (exponent >= 16 || exponent < -4) {
+            $dst.push_str(&scientific);
+            Ok(())
+        } else {
+            write!($dst, "{}", v).and_then(|()| {
+                if v % 1.0 == 0.0 {
+                    write!($dst, ".0")
+                } else {
+                    Ok(())
+                }
+            })
         }
-        Ok(())
-    }
+        .map_err(ser::Error::custom)
+    }};
+}
 
-    fn serialize_bytes(self, value: &[u8]) -> Result<(), Self::Error> {
-        value.serialize(self)
-    }
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) fn float_to_string(v: f64) -> String {
+    let mut dst = String::new();
+    let _: Result<(), Error> = write_float!(dst, v);
+    dst
+}
 
-    fn serialize_none(self) -> Result<(), Self::Error> {
-        Err(Error::UnsupportedNone)
-    }
+// A value as `Serializer` is going to write it. The data structure is
+// serialized into a tree of these once, so that the layout of each table entry
+// can be decided from its shape before any of it is written.
+enum Node {
+    None,
+    Scalar(Scalar),
+    // `()` or a unit struct.
+    Unit,
+    Array(Vec<Node>),
+    Table(Vec<(String, Node)>),
+    // A tuple variant.
+    Variant(&'static str, Vec<Node>),
+    // A value wrapped in `Inline`, `Dotted` or `Section`, by the name of the
+    // style.
+    Styled(&'static str, Box<Node>),
+    Commented(String, Box<Node>),
+}
 
-    fn serialize_some<T>(self, value: &T) -> Result<(), Self::Error>
-    where
-        T: ?Sized + Serialize,
-    {
-        value.serialize(self)
-    }
+enum Scalar {
+    // A string, with the style it was wrapped in if any.
+    Str(String, Option<StrStyle>),
+    Int(i128, IntFormat),
+    // Anything else, such as a boolean or a float, written out already.
+    Raw(String),
+}
 
-    fn serialize_unit(self) -> Result<(), Self::Error> {
-        Err(Error::UnsupportedType)
+impl Scalar {
+    fn emit(&self, dst: &mut String) -> Result<(), Error> {
+        match *self {
+            Scalar::Str(ref value, style) => {
+                emit_str(dst, value, style.unwrap_or_else(|| StrStyle::pick(value)))
+            }
+            Scalar::Int(value, format) => emit_int(dst, value, format),
+            Scalar::Raw(ref raw) => {
+                dst.push_str(raw);
+                Ok(())
+            }
+        }
     }
+}
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Self::Error> {
-        Err(Error::UnsupportedType)
+// The kind of a value as far as laying out a table is concerned.
+enum Shape {
+    Table,
+    // A non-empty array of tables, written as `[[header]]` sections.
+    ArrayOfTables,
+    // Any other array, written inline.
+    Array,
+    Other,
+}
+
+impl Node {
+    fn shape(&self) -> Shape {
+        match *self {
+            Node::Table(_) => Shape::Table,
+            Node::Array(ref nodes) => {
+                if !nodes.is_empty()
+                    && nodes
+                        .iter()
+                        .all(|node| matches!(node.shape(), Shape::Table))
+                {
+                    Shape::ArrayOfTables
+                } else {
+                    Shape::Array
+                }
+            }
+            Node::Styled(style::INLINE, _)
+            | Node::None
+            | Node::Scalar(_)
+            | Node::Unit
+            | Node::Variant(..) => Shape::Other,
+            Node::Styled(_, ref node) | Node::Commented(_, ref node) => node.shape(),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct NodeSerializer<'a> {
+    nulls: Option<&'a NullPolicy>,
+}
+
+struct SerializeNodeArray<'a> {
+    ser: NodeSerializer<'a>,
+    nodes: Vec<Node>,
+    // The name of the variant, for a tuple variant.
+    variant: Option<&'static str>,
+}
+
+struct SerializeNodeTable<'a> {
+    ser: NodeSerializer<'a>,
+    entries: Vec<(String, Node)>,
+    key: String,
+    // Whether the entries are the comment and value of a `Commented`.
+    commented: bool,
+}
+
+fn int(v: impl Into<i128>) -> Node {
+    Node::Scalar(Scalar::Int(v.into(), IntFormat::DECIMAL))
+}
+
+macro_rules! float {
+    ($v:expr) => {{
+        let mut raw = String::new();
+        write_float!(raw, $v)?;
+        Ok(Node::Scalar(Scalar::Raw(raw)))
+    }};
+}
+
+impl<'a> ser::Serializer for NodeSerializer<'a> {
+    type Ok = Node;
+    type Error = Error;
+    type SerializeSeq = SerializeNodeArray<'a>;
+    type SerializeTuple = SerializeNodeArray<'a>;
+    type SerializeTupleStruct = SerializeNodeArray<'a>;
+    type SerializeTupleVariant = SerializeNodeArray<'a>;
+    type SerializeMap = SerializeNodeTable<'a>;
+    type SerializeStruct = SerializeNodeTable<'a>;
+    type SerializeStructVariant = ser::Impossible<Node, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, Self::Error> {
+        Ok(Node::Scalar(Scalar::Raw(v.to_string())))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, Self::Error> {
+        Ok(int(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, Self::Error> {
+        float!(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, Self::Error> {
+        float!(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, Self::Error> {
+        Ok(Node::Scalar(Scalar::Str(v.to_string(), None)))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Node, Self::Error> {
+        Ok(Node::Scalar(Scalar::Str(value.to_owned(), None)))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Node, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_none(self) -> Result<Node, Self::Error> {
+        match self.nulls {
+            Some(NullPolicy::Placeholder(placeholder)) => self.serialize_str(placeholder),
+            _ => Ok(Node::None),
+        }
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Node, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, Self::Error> {
+        // Such as a JSON null.
+        if self.nulls.is_some() {
+            return self.serialize_none();
+        }
+        Ok(Node::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Node, Self::Error> {
+        Ok(Node::Unit)
     }
 
     fn serialize_unit_variant(
@@ -405,15 +1294,37 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
         _name: &'static str,
         _variant_index: u32,
         variant: &'static str,
-    ) -> Result<(), Self::Error> {
+    ) -> Result<Node, Self::Error> {
         self.serialize_str(variant)
     }
 
-    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Self::Error>
+    fn serialize_newtype_struct<T>(self, name: &'static str, value: &T) -> Result<Node, Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(self)
+        #[cfg(feature = "arbitrary_precision")]
+        if name == crate::number::TOKEN {
+            let lexeme = value.serialize(StringExtractor)?;
+            return Ok(Node::Scalar(Scalar::Raw(lexeme)));
+        }
+        let node = value.serialize(self)?;
+        Ok(match (name, node) {
+            (style::INLINE | style::DOTTED | style::SECTION, node) => {
+                Node::Styled(name, Box::new(node))
+            }
+            // The styles of strings and integers are ignored for other values.
+            (_, Node::Scalar(Scalar::Str(value, style))) => {
+                Node::Scalar(Scalar::Str(value, StrStyle::from_name(name).or(style)))
+            }
+            (_, Node::Scalar(Scalar::Int(value, format))) => {
+                let format = match IntFormat::from_name(name) {
+                    Some(outer) => format.within(outer),
+                    None => format,
+                };
+                Node::Scalar(Scalar::Int(value, format))
+            }
+            (_, node) => node,
+        })
     }
 
     fn serialize_newtype_variant<T>(
@@ -422,7 +1333,7 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
         _variant_index: u32,
         _variant: &'static str,
         _value: &T,
-    ) -> Result<(), Self::Error>
+    ) -> Result<Node, Self::Error>
     where
         T: ?Sized + Serialize,
     {
@@ -430,12 +1341,10 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
     }
 
     fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        self.array_type(ArrayState::Started);
-        Ok(SerializeSeq {
+        Ok(SerializeNodeArray {
             ser: self,
-            first: Cell::new(true),
-            type_: Cell::new(None),
-            len,
+            nodes: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None,
         })
     }
 
@@ -455,34 +1364,33 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        Err(Error::UnsupportedType)
+        Ok(SerializeNodeArray {
+            ser: self,
+            nodes: Vec::with_capacity(len),
+            variant: Some(variant),
+        })
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        self.array_type(ArrayState::StartedAsATable);
-        Ok(SerializeTable {
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeNodeTable {
             ser: self,
+            entries: Vec::with_capacity(len.unwrap_or(0)),
             key: String::new(),
-            first: Cell::new(true),
-            table_emitted: Cell::new(false),
+            commented: false,
         })
     }
 
     fn serialize_struct(
         self,
-        _name: &'static str,
-        _len: usize,
+        name: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        self.array_type(ArrayState::StartedAsATable);
-        Ok(SerializeTable {
-            ser: self,
-            key: String::new(),
-            first: Cell::new(true),
-            table_emitted: Cell::new(false),
-        })
+        let mut table = self.serialize_map(Some(len))?;
+        table.commented = name == style::COMMENTED;
+        Ok(table)
     }
 
     fn serialize_struct_variant(
@@ -496,46 +1404,28 @@ impl<'a, 'b> ser::Serializer for &'b mut Serializer<'a> {
     }
 }
 
-impl<'a, 'b> ser::SerializeSeq for SerializeSeq<'a, 'b> {
-    type Ok = ();
+impl ser::SerializeSeq for SerializeNodeArray<'_> {
+    type Ok = Node;
     type Error = Error;
 
     fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut Serializer {
-            dst: &mut *self.ser.dst,
-            state: State::Array {
-                parent: &self.ser.state,
-                first: &self.first,
-                type_: &self.type_,
-                len: self.len,
-            },
-        })?;
-        self.first.set(false);
+        self.nodes.push(value.serialize(self.ser)?);
         Ok(())
     }
 
-    fn end(self) -> Result<(), Error> {
-        match self.type_.get() {
-            Some(ArrayState::StartedAsATable) => return Ok(()),
-            Some(ArrayState::Started) => self.ser.dst.push(']'),
-            None => {
-                assert!(self.first.get());
-                self.ser.emit_key(ArrayState::Started)?;
-                self.ser.dst.push_str("[]");
-            }
-        }
-        if let State::Table { .. } = self.ser.state {
-            self.ser.dst.push('\n');
-        }
-        Ok(())
+    fn end(self) -> Result<Node, Error> {
+        Ok(match self.variant {
+            Some(variant) => Node::Variant(variant, self.nodes),
+            None => Node::Array(self.nodes),
+        })
     }
 }
 
-impl<'a, 'b> ser::SerializeTuple for SerializeSeq<'a, 'b> {
-    type Ok = ();
+impl ser::SerializeTuple for SerializeNodeArray<'_> {
+    type Ok = Node;
     type Error = Error;
 
     fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
@@ -545,13 +1435,13 @@ impl<'a, 'b> ser::SerializeTuple for SerializeSeq<'a, 'b> {
         ser::SerializeSeq::serialize_element(self, value)
     }
 
-    fn end(self) -> Result<(), Error> {
+    fn end(self) -> Result<Node, Error> {
         ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a, 'b> ser::SerializeTupleStruct for SerializeSeq<'a, 'b> {
-    type Ok = ();
+impl ser::SerializeTupleStruct for SerializeNodeArray<'_> {
+    type Ok = Node;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
@@ -561,13 +1451,29 @@ impl<'a, 'b> ser::SerializeTupleStruct for SerializeSeq<'a, 'b> {
         ser::SerializeSeq::serialize_element(self, value)
     }
 
-    fn end(self) -> Result<(), Error> {
+    fn end(self) -> Result<Node, Error> {
         ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a, 'b> ser::SerializeMap for SerializeTable<'a, 'b> {
-    type Ok = ();
+impl ser::SerializeTupleVariant for SerializeNodeArray<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeMap for SerializeNodeTable<'_> {
+    type Ok = Node;
     type Error = Error;
 
     fn serialize_key<T>(&mut self, input: &T) -> Result<(), Error>
@@ -582,64 +1488,612 @@ impl<'a, 'b> ser::SerializeMap for SerializeTable<'a, 'b> {
     where
         T: ?Sized + Serialize,
     {
-        let res = value.serialize(&mut Serializer {
-            dst: &mut *self.ser.dst,
-            state: State::Table {
-                key: &self.key,
-                parent: &self.ser.state,
-                first: &self.first,
-                table_emitted: &self.table_emitted,
-            },
-        });
-        match res {
-            Ok(()) => self.first.set(false),
-            Err(Error::UnsupportedNone) => {}
+        let key = std::mem::take(&mut self.key);
+        self.entries.push((key, value.serialize(self.ser)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Ok(Node::Table(self.entries))
+    }
+}
+
+impl ser::SerializeStruct for SerializeNodeTable<'_> {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.entries
+            .push((key.to_owned(), value.serialize(self.ser)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        if !self.commented {
+            return Ok(Node::Table(self.entries));
+        }
+        let mut fields = self.entries.into_iter().map(|(_, node)| node);
+        match (fields.next(), fields.next()) {
+            (Some(Node::Scalar(Scalar::Str(comment, _))), Some(value)) => {
+                Ok(Node::Commented(comment, Box::new(value)))
+            }
+            _ => Err(Error::UnsupportedType),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ValueSerializer<'a> {
+    nulls: Option<&'a NullPolicy>,
+    // Whether newtype and struct variants are supported.
+    variants: bool,
+}
+
+struct SerializeValueArray<'a> {
+    ser: ValueSerializer<'a>,
+    values: Vec<Value<'static>>,
+}
+
+struct SerializeValueTable<'a> {
+    ser: ValueSerializer<'a>,
+    values: Vec<TablePair<'static>>,
+    key: String,
+    // Whether the fields are the comment and value of a `Commented`, of which
+    // only the value is kept.
+    commented: bool,
+}
+
+struct SerializeValueVariant<'a> {
+    ser: ValueSerializer<'a>,
+    variant: &'static str,
+    values: Vec<Value<'static>>,
+}
+
+struct SerializeValueStructVariant<'a> {
+    variant: &'static str,
+    table: SerializeValueTable<'a>,
+}
+
+fn value(e: E<'static>) -> Value<'static> {
+    Value {
+        e,
+        start: 0,
+        end: 0,
+    }
+}
+
+// A table with the single key `variant`, for an enum variant holding `data`.
+fn variant(variant: &'static str, data: Value<'static>) -> Value<'static> {
+    let key = (Span { start: 0, end: 0 }, Cow::Borrowed(variant));
+    value(E::InlineTable(vec![(key, data)]))
+}
+
+impl ValueSerializer<'_> {
+    fn integer<T>(v: T) -> Result<Value<'static>, Error>
+    where
+        T: TryInto<i64> + Display + Copy,
+    {
+        match v.try_into() {
+            Ok(i) => Ok(value(E::Integer(i))),
+            Err(_) => Err(ser::Error::custom(format_args!(
+                "integer {} is out of range of a TOML integer",
+                v,
+            ))),
+        }
+    }
+}
+
+impl<'a> ser::Serializer for ValueSerializer<'a> {
+    type Ok = Value<'static>;
+    type Error = Error;
+    type SerializeSeq = SerializeValueArray<'a>;
+    type SerializeTuple = SerializeValueArray<'a>;
+    type SerializeTupleStruct = SerializeValueArray<'a>;
+    type SerializeTupleVariant = SerializeValueVariant<'a>;
+    type SerializeMap = SerializeValueTable<'a>;
+    type SerializeStruct = SerializeValueTable<'a>;
+    type SerializeStructVariant = SerializeValueStructVariant<'a>;
+
+    fn serialize_bool(self, v: bool) -> Result<Value<'static>, Self::Error> {
+        Ok(value(E::Boolean(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value<'static>, Self::Error> {
+        Self::integer(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value<'static>, Self::Error> {
+        // Go through the shortest representation of the f32, which converts
+        // back to the same f32, so that 0.1f32 becomes 0.1 rather than
+        // 0.10000000149011612.
+        let v = if v.is_nan() {
+            f64::NAN.copysign(f64::from(v.signum()))
+        } else {
+            v.to_string().parse().unwrap()
+        };
+        Ok(value(E::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value<'static>, Self::Error> {
+        Ok(value(E::Float(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value<'static>, Self::Error> {
+        Ok(value(E::String(Cow::Owned(v.to_string()))))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value<'static>, Self::Error> {
+        Ok(value(E::String(Cow::Owned(v.to_owned()))))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value<'static>, Self::Error> {
+        v.serialize(self)
+    }
+
+    fn serialize_none(self) -> Result<Value<'static>, Self::Error> {
+        match self.nulls {
+            Some(NullPolicy::Placeholder(placeholder)) => self.serialize_str(placeholder),
+            _ => Err(Error::UnsupportedNone),
+        }
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value<'static>, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value<'static>, Self::Error> {
+        // Such as a JSON null.
+        if self.nulls.is_some() {
+            return self.serialize_none();
+        }
+        Ok(value(E::InlineTable(Vec::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value<'static>, Self::Error> {
+        Ok(value(E::InlineTable(Vec::new())))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value<'static>, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    #[cfg_attr(not(feature = "arbitrary_precision"), allow(unused_variables))]
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value<'static>, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        #[cfg(feature = "arbitrary_precision")]
+        if name == crate::number::TOKEN {
+            let lexeme = value.serialize(StringExtractor)?;
+            return match crate::de::parse_number(&lexeme) {
+                Ok(number) => Ok(number.into_owned(0)),
+                Err(err) => Err(ser::Error::custom(err)),
+            };
+        }
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant_name: &'static str,
+        value: &T,
+    ) -> Result<Value<'static>, Self::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if !self.variants {
+            return Err(Error::UnsupportedType);
+        }
+        Ok(variant(variant_name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeValueArray {
+            ser: self,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeValueVariant {
+            ser: self,
+            variant,
+            values: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeValueTable {
+            ser: self,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+            key: String::new(),
+            commented: false,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        let mut table = self.serialize_map(Some(len))?;
+        table.commented = name == style::COMMENTED;
+        Ok(table)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        if !self.variants {
+            return Err(Error::UnsupportedType);
+        }
+        Ok(SerializeValueStructVariant {
+            variant,
+            table: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeValueArray<'_> {
+    type Ok = Value<'static>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match value.serialize(self.ser) {
+            Ok(value) => self.values.push(value),
+            Err(Error::UnsupportedNone) => skip_none(self.ser.nulls, false)?,
             Err(e) => return Err(e),
         }
         Ok(())
     }
 
-    fn end(self) -> Result<(), Error> {
-        if self.first.get() {
-            let state = self.ser.state.clone();
-            self.ser.emit_table_header(&state)?;
+    fn end(self) -> Result<Value<'static>, Error> {
+        Ok(value(E::Array(self.values)))
+    }
+}
+
+impl ser::SerializeTuple for SerializeValueArray<'_> {
+    type Ok = Value<'static>;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value<'static>, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeValueArray<'_> {
+    type Ok = Value<'static>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value<'static>, Error> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeValueVariant<'_> {
+    type Ok = Value<'static>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match value.serialize(self.ser) {
+            Ok(value) => self.values.push(value),
+            Err(Error::UnsupportedNone) => skip_none(self.ser.nulls, false)?,
+            Err(e) => return Err(e),
         }
         Ok(())
     }
+
+    fn end(self) -> Result<Value<'static>, Error> {
+        Ok(variant(self.variant, value(E::Array(self.values))))
+    }
 }
 
-impl<'a, 'b> ser::SerializeStruct for SerializeTable<'a, 'b> {
-    type Ok = ();
+impl ser::SerializeStructVariant for SerializeValueStructVariant<'_> {
+    type Ok = Value<'static>;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
     where
         T: ?Sized + Serialize,
     {
-        let res = value.serialize(&mut Serializer {
-            dst: &mut *self.ser.dst,
-            state: State::Table {
-                key,
-                parent: &self.ser.state,
-                first: &self.first,
-                table_emitted: &self.table_emitted,
-            },
-        });
-        match res {
-            Ok(()) => self.first.set(false),
-            Err(Error::UnsupportedNone) => {}
-            Err(e) => return Err(e),
+        self.table.insert(key.to_owned(), value)
+    }
+
+    fn end(self) -> Result<Value<'static>, Error> {
+        let table = ser::SerializeMap::end(self.table)?;
+        Ok(variant(self.variant, table))
+    }
+}
+
+impl SerializeValueTable<'_> {
+    fn insert<T>(&mut self, key: String, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        match value.serialize(self.ser) {
+            Ok(value) => {
+                let span = Span { start: 0, end: 0 };
+                self.values.push(((span, Cow::Owned(key)), value));
+                Ok(())
+            }
+            Err(Error::UnsupportedNone) => skip_none(self.ser.nulls, true),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl ser::SerializeMap for SerializeValueTable<'_> {
+    type Ok = Value<'static>;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, input: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = input.serialize(StringExtractor)?;
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = std::mem::take(&mut self.key);
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value<'static>, Error> {
+        Ok(value(E::InlineTable(self.values)))
+    }
+}
+
+impl ser::SerializeStruct for SerializeValueTable<'_> {
+    type Ok = Value<'static>;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        if !self.commented {
+            return self.insert(key.to_owned(), value);
+        }
+        if key == "value" {
+            // A missing value is left to the enclosing table or array, as if
+            // the `Commented` were not there.
+            let value = value.serialize(self.ser)?;
+            let span = Span { start: 0, end: 0 };
+            self.values.push(((span, Cow::Borrowed(key)), value));
         }
         Ok(())
     }
 
-    fn end(self) -> Result<(), Error> {
-        if self.first.get() {
-            let state = self.ser.state.clone();
-            self.ser.emit_table_header(&state)?;
+    fn end(mut self) -> Result<Value<'static>, Error> {
+        if !self.commented {
+            return ser::SerializeMap::end(self);
+        }
+        match self.values.pop() {
+            Some((_, value)) => Ok(value),
+            None => Err(Error::UnsupportedType),
+        }
+    }
+}
+
+struct Canonical<'a> {
+    dst: &'a mut String,
+}
+
+fn is_table(value: &Value) -> bool {
+    match value.e {
+        E::InlineTable(_) | E::DottedTable(_) => true,
+        _ => false,
+    }
+}
+
+fn is_array_of_tables(value: &Value) -> bool {
+    match &value.e {
+        E::Array(values) => !values.is_empty() && values.iter().all(is_table),
+        _ => false,
+    }
+}
+
+fn sorted(mut values: Vec<TablePair>) -> Result<Vec<TablePair>, Error> {
+    values.sort_by(|a, b| a.0 .1.cmp(&b.0 .1));
+    for pair in values.windows(2) {
+        if pair[0].0 .1 == pair[1].0 .1 {
+            let msg = format!("duplicate key: `{}`", pair[0].0 .1);
+            return Err(Error::Custom(msg));
+        }
+    }
+    Ok(values)
+}
+
+impl<'a> Canonical<'a> {
+    // Emits the table at `path`, with a `[[header]]` if it is an element of an
+    // array of tables and a `[header]` if it has any key/value pairs of its own.
+    fn table(
+        &mut self,
+        path: &mut Vec<String>,
+        values: Vec<TablePair>,
+        array: bool,
+    ) -> Result<(), Error> {
+        let values = sorted(values)?;
+        let (tables, values): (Vec<_>, Vec<_>) = values
+            .into_iter()
+            .partition(|(_, value)| is_table(value) || is_array_of_tables(value));
+
+        if array || (!path.is_empty() && (!values.is_empty() || tables.is_empty())) {
+            if !self.dst.is_empty() {
+                self.dst.push('\n');
+            }
+            self.dst.push_str(if array { "[[" } else { "[" });
+            for (i, key) in path.iter().enumerate() {
+                if i > 0 {
+                    self.dst.push('.');
+                }
+                escape_key(self.dst, key)?;
+            }
+            self.dst.push_str(if array { "]]\n" } else { "]\n" });
+        }
+
+        for ((_, key), value) in values {
+            escape_key(self.dst, &key)?;
+            self.dst.push_str(" = ");
+            self.value(value)?;
+            self.dst.push('\n');
+        }
+
+        for ((_, key), value) in tables {
+            path.push(key.into_owned());
+            match value.e {
+                E::InlineTable(values) | E::DottedTable(values) => {
+                    self.table(path, values, false)?;
+                }
+                E::Array(array) => {
+                    for value in array {
+                        if let E::InlineTable(values) | E::DottedTable(values) = value.e {
+                            self.table(path, values, true)?;
+                        }
+                    }
+                }
+                _ => unreachable!(),
+            }
+            path.pop();
         }
         Ok(())
     }
+
+    fn value(&mut self, value: Value) -> Result<(), Error> {
+        match value.e {
+            E::Integer(i) => write!(self.dst, "{}", i).map_err(ser::Error::custom),
+            E::Float(f) => write_float!(self.dst, f),
+            E::Boolean(b) => write!(self.dst, "{}", b).map_err(ser::Error::custom),
+            E::String(s) => emit_basic_str(self.dst, &s),
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(_, Some(e)) => self.value(Value { e: *e, ..value }),
+            #[cfg(feature = "arbitrary_precision")]
+            E::Number(lexeme, None) => {
+                self.dst.push_str(&lexeme);
+                Ok(())
+            }
+            E::Array(values) => {
+                self.dst.push('[');
+                for (i, value) in values.into_iter().enumerate() {
+                    if i > 0 {
+                        self.dst.push_str(", ");
+                    }
+                    self.value(value)?;
+                }
+                self.dst.push(']');
+                Ok(())
+            }
+            E::InlineTable(values) | E::DottedTable(values) => {
+                let values = sorted(values)?;
+                if values.is_empty() {
+                    self.dst.push_str("{}");
+                    return Ok(());
+                }
+                self.dst.push_str("{ ");
+                for (i, ((_, key), value)) in values.into_iter().enumerate() {
+                    if i > 0 {
+                        self.dst.push_str(", ");
+                    }
+                    escape_key(self.dst, &key)?;
+                    self.dst.push_str(" = ");
+                    self.value(value)?;
+                }
+                self.dst.push_str(" }");
+                Ok(())
+            }
+        }
+    }
 }
 
 struct StringExtractor;
@@ -819,6 +2273,7 @@ impl Display for Error {
             Error::KeyNotString => "map key was not a string".fmt(f),
             Error::ValueAfterTable => "values must be emitted before tables".fmt(f),
             Error::UnsupportedNone => "unsupported None value".fmt(f),
+            Error::SectionNotAllowed => "table cannot be written as a section here".fmt(f),
             Error::Custom(ref s) => s.fmt(f),
         }
     }
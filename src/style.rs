@@ -0,0 +1,327 @@
+//! Wrapper types for controlling how individual values are written.
+//!
+//! By default the serializer picks the most readable form of a string on its
+//! own: a literal string `'...'` for strings containing backslashes, a
+//! multi-line string for strings containing newlines, and a basic string
+//! `"..."` otherwise. Wrapping a field in one of the types below forces a
+//! particular style instead.
+//!
+//! ```
+//! use basic_toml::style::{Basic, Literal};
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     pattern: Basic<String>,
+//!     path: Literal<String>,
+//! }
+//!
+//! let config = Config {
+//!     pattern: Basic(r"\d+".to_owned()),
+//!     path: Literal("/var/log".to_owned()),
+//! };
+//!
+//! let toml = basic_toml::to_string(&config).unwrap();
+//! assert_eq!(toml, "pattern = \"\\\\d+\"\npath = '/var/log'\n");
+//! ```
+//!
+//! Integers are written in decimal unless wrapped in [`Hex`], [`Octal`] or
+//! [`Binary`], and [`Grouped`] separates their digits with underscores.
+//!
+//! ```
+//! use basic_toml::style::{Grouped, Hex, Octal};
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Permissions {
+//!     mode: Octal<u32>,
+//!     mask: Hex<Grouped<u32>>,
+//!     limit: Grouped<u64>,
+//! }
+//!
+//! let permissions = Permissions {
+//!     mode: Octal(0o755),
+//!     mask: Hex(Grouped(0xDEAD_BEEF)),
+//!     limit: Grouped(1_000_000),
+//! };
+//!
+//! let toml = basic_toml::to_string(&permissions).unwrap();
+//! assert_eq!(toml, "mode = 0o755\nmask = 0xDEAD_BEEF\nlimit = 1_000_000\n");
+//! ```
+//!
+//! Tables nested in other tables are written under a `[header]` of their own
+//! unless wrapped in [`Inline`] or [`Dotted`].
+//!
+//! ```
+//! use basic_toml::style::{Dotted, Inline};
+//! use serde::Serialize;
+//! use std::collections::BTreeMap;
+//!
+//! #[derive(Serialize)]
+//! struct Manifest {
+//!     lints: Dotted<Lints>,
+//!     dependencies: BTreeMap<&'static str, Inline<Dependency>>,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct Lints {
+//!     rust: BTreeMap<&'static str, &'static str>,
+//! }
+//!
+//! #[derive(Serialize)]
+//! struct Dependency {
+//!     version: &'static str,
+//!     features: Vec<&'static str>,
+//! }
+//!
+//! let mut rust = BTreeMap::new();
+//! rust.insert("unsafe_code", "forbid");
+//!
+//! let mut dependencies = BTreeMap::new();
+//! let serde = Dependency {
+//!     version: "1",
+//!     features: vec!["derive"],
+//! };
+//! dependencies.insert("serde", Inline(serde));
+//!
+//! let manifest = Manifest {
+//!     lints: Dotted(Lints { rust }),
+//!     dependencies,
+//! };
+//!
+//! let toml = basic_toml::to_string(&manifest).unwrap();
+//! let expected = r#"lints.rust.unsafe_code = "forbid"
+//!
+//! [dependencies]
+//! serde = { version = "1", features = ["derive"] }
+//! "#;
+//! assert_eq!(toml, expected);
+//! ```
+//!
+//! The wrappers are transparent to deserialization. Apart from `Commented`,
+//! which other serializers see as a struct of its comment and value, they are
+//! also transparent to serializers other than the one in this crate.
+
+use serde::de::{Deserialize, Deserializer};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+macro_rules! style {
+    ($(#[$doc:meta])* $name:ident, $token:ident = $magic:expr) => {
+        $(#[$doc])*
+        #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name<T>(pub T);
+
+        pub(crate) const $token: &str = $magic;
+
+        impl<T> Serialize for $name<T>
+        where
+            T: Serialize,
+        {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: Serializer,
+            {
+                serializer.serialize_newtype_struct($token, &self.0)
+            }
+        }
+
+        impl<'de, T> Deserialize<'de> for $name<T>
+        where
+            T: Deserialize<'de>,
+        {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                T::deserialize(deserializer).map($name)
+            }
+        }
+    };
+}
+
+style! {
+    /// Writes a string as a basic string, `"..."`, escaping whatever needs it.
+    Basic, BASIC = "$__basic_toml_private_style_basic"
+}
+
+style! {
+    /// Writes a string as a literal string, `'...'`.
+    ///
+    /// Serialization fails if the string contains a single quote, a newline or
+    /// another control character other than tab, none of which a literal
+    /// string can hold.
+    Literal, LITERAL = "$__basic_toml_private_style_literal"
+}
+
+style! {
+    /// Writes a string as a multi-line basic string, `"""..."""`.
+    MultilineBasic, MULTILINE_BASIC = "$__basic_toml_private_style_multiline_basic"
+}
+
+style! {
+    /// Writes a string as a multi-line literal string, `'''...'''`.
+    ///
+    /// Serialization fails if the string contains `'''`, ends with a single
+    /// quote, or contains a control character other than tab and newline.
+    MultilineLiteral, MULTILINE_LITERAL = "$__basic_toml_private_style_multiline_literal"
+}
+
+style! {
+    /// Writes an integer in hexadecimal, `0xFF`.
+    ///
+    /// Serialization fails for negative integers, which TOML only allows in
+    /// decimal.
+    Hex, HEX = "$__basic_toml_private_style_hex"
+}
+
+style! {
+    /// Writes an integer in octal, `0o755`.
+    ///
+    /// Serialization fails for negative integers, which TOML only allows in
+    /// decimal.
+    Octal, OCTAL = "$__basic_toml_private_style_octal"
+}
+
+style! {
+    /// Writes an integer in binary, `0b1010`.
+    ///
+    /// Serialization fails for negative integers, which TOML only allows in
+    /// decimal.
+    Binary, BINARY = "$__basic_toml_private_style_binary"
+}
+
+style! {
+    /// Writes an integer with underscores between groups of digits,
+    /// `1_000_000`.
+    ///
+    /// Decimal and octal digits are grouped in threes, hexadecimal and binary
+    /// digits in fours. Combine with another integer style to group its
+    /// digits, as in `Hex(Grouped(0xFFFF00))` which is written as `0xFF_FF00`.
+    Grouped, GROUPED = "$__basic_toml_private_style_grouped"
+}
+
+style! {
+    /// Writes a table or array of tables inline, `key = { a = 1, b = 2 }`,
+    /// rather than under a `[header]`.
+    ///
+    /// Like other values, inline tables have to come before any of the tables
+    /// written with a header in the same table.
+    Inline, INLINE = "$__basic_toml_private_style_inline"
+}
+
+style! {
+    /// Writes a table under a `[header]`, which is the default for tables
+    /// nested in other tables.
+    ///
+    /// Serialization fails if the table is somewhere that a header cannot be
+    /// written, such as inside an inline table, an array that is not an array
+    /// of tables, or a table wrapped in [`Dotted`].
+    Section, SECTION = "$__basic_toml_private_style_section"
+}
+
+style! {
+    /// Writes a table as dotted keys in its parent table, `a.b.c = 1`, rather
+    /// than under a `[header]`.
+    ///
+    /// Tables nested in a dotted table are dotted too, while arrays are written
+    /// inline. Like other values, dotted keys have to come before any of the
+    /// tables written with a header in the same table.
+    Dotted, DOTTED = "$__basic_toml_private_style_dotted"
+}
+
+/// Writes a comment above a key/value pair, above a table header, or at the
+/// top of the document when wrapped around the whole value being serialized.
+///
+/// ```
+/// use basic_toml::style::Commented;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Config {
+///     retries: Commented<u32>,
+///     server: Commented<Server>,
+/// }
+///
+/// #[derive(Serialize)]
+/// struct Server {
+///     port: u16,
+/// }
+///
+/// let config = Config {
+///     retries: Commented::new("How many times to retry a failed request.", 3),
+///     server: Commented::new("Where to listen.", Server { port: 8080 }),
+/// };
+/// let config = Commented::new("Managed by deploy-bot, do not edit.", config);
+///
+/// let toml = basic_toml::to_string(&config).unwrap();
+/// let expected = "\
+/// ## Managed by deploy-bot, do not edit.
+///
+/// ## How many times to retry a failed request.
+/// retries = 3
+///
+/// ## Where to listen.
+/// [server]
+/// port = 8080
+/// ";
+/// assert_eq!(toml, expected);
+/// ```
+///
+/// Each line of the comment becomes a line starting with `#`. Comments on
+/// values which are written inline, such as the elements of an inline array or
+/// the entries of an inline or dotted table, are left out. Serialization fails
+/// if the comment contains a control character other than tab or a line
+/// break.
+///
+/// Deserializing a `Commented` deserializes the value and leaves the comment
+/// empty. Serializers other than the one in this crate see a struct with a
+/// `comment` and a `value` field.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Commented<T> {
+    /// The text of the comment, without the leading `#`.
+    pub comment: String,
+    /// The value the comment is written above.
+    pub value: T,
+}
+
+pub(crate) const COMMENTED: &str = "$__basic_toml_private_style_commented";
+
+impl<T> Commented<T> {
+    /// Attaches a comment to a value.
+    pub fn new(comment: impl Into<String>, value: T) -> Self {
+        Commented {
+            comment: comment.into(),
+            value,
+        }
+    }
+}
+
+impl<T> Serialize for Commented<T>
+where
+    T: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct(COMMENTED, 2)?;
+        state.serialize_field("comment", &self.comment)?;
+        state.serialize_field("value", &self.value)?;
+        state.end()
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Commented<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(|value| Commented {
+            comment: String::new(),
+            value,
+        })
+    }
+}
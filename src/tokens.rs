@@ -63,6 +63,7 @@ pub enum Error {
 pub struct Tokenizer<'a> {
     input: &'a str,
     chars: CrlfFold<'a>,
+    escapes_1_1: bool,
 }
 
 #[derive(Clone)]
@@ -83,12 +84,19 @@ impl<'a> Tokenizer<'a> {
             chars: CrlfFold {
                 chars: input.char_indices(),
             },
+            escapes_1_1: false,
         };
         // Eat utf-8 BOM
         t.eatc('\u{feff}');
         t
     }
 
+    /// Accepts the `\e` and `\xHH` escapes added in TOML 1.1 in basic strings.
+    pub fn with_1_1_escapes(mut self) -> Tokenizer<'a> {
+        self.escapes_1_1 = true;
+        self
+    }
+
     pub fn next(&mut self) -> Result<Option<(Span, Token<'a>)>, Error> {
         let (start, token) = match self.one() {
             Some((start, '\n')) => (start, Token::Newline),
@@ -266,7 +274,7 @@ impl<'a> Tokenizer<'a> {
 
     fn comment_token(&mut self, start: usize) -> Token<'a> {
         while let Some((_, ch)) = self.chars.clone().next() {
-            if ch != '\t' && (ch < '\u{20}' || ch > '\u{10ffff}') {
+            if ch != '\t' && (ch < '\u{20}' || ch == '\u{7f}') {
                 break;
             }
             self.one();
@@ -372,10 +380,12 @@ impl<'a> Tokenizer<'a> {
                     Some((_, 'n')) => val.push('\n'),
                     Some((_, 'r')) => val.push('\r'),
                     Some((_, 't')) => val.push('\t'),
+                    Some((_, 'e')) if me.escapes_1_1 => val.push('\u{1b}'),
                     Some((i, c @ ('u' | 'U'))) => {
                         let len = if c == 'u' { 4 } else { 8 };
                         val.push(me.hex(start, i, len)?);
                     }
+                    Some((i, 'x')) if me.escapes_1_1 => val.push(me.hex(start, i, 2)?),
                     Some((i, c @ (' ' | '\t' | '\n'))) if multi => {
                         if c != '\n' {
                             while let Some((_, ch)) = me.chars.clone().next() {
@@ -501,7 +511,7 @@ impl MaybeString {
         }
     }
 
-    fn into_cow(self, input: &str) -> Cow<str> {
+    fn into_cow(self, input: &str) -> Cow<'_, str> {
         match self {
             MaybeString::NotEscaped(start) => Cow::Borrowed(&input[start..]),
             MaybeString::Owned(s) => Cow::Owned(s),
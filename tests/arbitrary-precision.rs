@@ -0,0 +1,89 @@
+#![cfg(feature = "arbitrary_precision")]
+#![allow(clippy::float_cmp)]
+
+use basic_toml::Number;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Prices {
+    exact: Number,
+    big: Number,
+    hex: Number,
+    normal: f64,
+    count: i64,
+}
+
+#[test]
+fn lexemes() {
+    let toml = "\
+exact = 0.1000000000000000055511151231257827
+big = 123_456_789_012_345_678_901_234_567_890
+hex = 0xdead_beef
+normal = 1.5
+count = 3
+";
+    let prices: Prices = basic_toml::from_str(toml).unwrap();
+    assert_eq!(
+        prices.exact.as_str(),
+        "0.1000000000000000055511151231257827"
+    );
+    assert_eq!(
+        prices.big.as_str(),
+        "123_456_789_012_345_678_901_234_567_890"
+    );
+    assert_eq!(prices.hex.to_string(), "0xdead_beef");
+    assert_eq!(prices.normal, 1.5);
+    assert_eq!(prices.count, 3);
+
+    assert_eq!(basic_toml::to_string(&prices).unwrap(), toml);
+}
+
+#[test]
+fn inline() {
+    let toml = "numbers = [1e1000, -0.0, +inf]\n";
+    let numbers: BTreeMap<String, Vec<Number>> = basic_toml::from_str(toml).unwrap();
+    let lexemes: Vec<&str> = numbers["numbers"].iter().map(Number::as_str).collect();
+    assert_eq!(lexemes, ["1e1000", "-0.0", "+inf"]);
+    assert_eq!(basic_toml::to_string(&numbers).unwrap(), toml);
+}
+
+#[test]
+fn out_of_range() {
+    let toml = "n = 99999999999999999999";
+    let value = basic_toml::from_str::<BTreeMap<String, Number>>(toml).unwrap();
+    assert_eq!(value["n"].as_str(), "99999999999999999999");
+
+    let err = basic_toml::from_str::<BTreeMap<String, i64>>(toml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "integer literal exceeds i64::MAX for key `n` at line 1 column 5"
+    );
+}
+
+#[test]
+fn not_a_number() {
+    let err = basic_toml::from_str::<BTreeMap<String, Number>>("n = '1'").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected a number, found string for key `n` at line 1 column 5",
+    );
+}
+
+#[test]
+fn from_str() {
+    assert_eq!("1_000".parse::<Number>().unwrap().as_str(), "1_000");
+    assert_eq!(" 1.5 ".parse::<Number>().unwrap().as_str(), "1.5");
+    assert!("1__0".parse::<Number>().is_err());
+    assert!("true".parse::<Number>().is_err());
+}
+
+#[test]
+fn other_formats() {
+    let number: Number = serde_json::from_str("2.5").unwrap();
+    assert_eq!(number.as_str(), "2.5");
+    let number: Number = serde_json::from_str("1e300").unwrap();
+    assert_eq!(number.as_str(), "1e300");
+    let number: Number = serde_json::from_str("18446744073709551615").unwrap();
+    assert_eq!(number.as_str(), "18446744073709551615");
+}
@@ -0,0 +1,113 @@
+use serde::Serialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize)]
+struct Manifest {
+    package: Package,
+    name: &'static str,
+    dependencies: HashMap<&'static str, &'static str>,
+    bin: Vec<Bin>,
+}
+
+#[derive(Serialize)]
+struct Package {
+    version: &'static str,
+    edition: u32,
+}
+
+#[derive(Serialize)]
+struct Bin {
+    path: &'static str,
+    name: &'static str,
+}
+
+#[test]
+fn sorted_keys() {
+    let manifest = Manifest {
+        package: Package {
+            version: "1.0.0",
+            edition: 2021,
+        },
+        name: "demo",
+        dependencies: HashMap::from([("serde", "1"), ("anyhow", "1"), ("syn", "2")]),
+        bin: vec![
+            Bin {
+                path: "src/main.rs",
+                name: "demo",
+            },
+            Bin {
+                path: "src/bin/other.rs",
+                name: "other",
+            },
+        ],
+    };
+
+    let expected = "\
+name = \"demo\"
+
+[[bin]]
+name = \"demo\"
+path = \"src/main.rs\"
+
+[[bin]]
+name = \"other\"
+path = \"src/bin/other.rs\"
+
+[dependencies]
+anyhow = \"1\"
+serde = \"1\"
+syn = \"2\"
+
+[package]
+edition = 2021
+version = \"1.0.0\"
+";
+    assert_eq!(
+        basic_toml::to_string_canonical(&manifest).unwrap(),
+        expected
+    );
+}
+
+#[test]
+fn independent_of_representation() {
+    #[derive(Serialize)]
+    struct Point {
+        y: f64,
+        x: f64,
+    }
+
+    let from_struct =
+        basic_toml::to_string_canonical(&BTreeMap::from([("point", Point { y: 2.0, x: 1.5 })]))
+            .unwrap();
+    let from_map = basic_toml::to_string_canonical(&json!({
+        "point": { "x": 1.5, "y": 2.0 },
+    }))
+    .unwrap();
+    assert_eq!(from_struct, "[point]\nx = 1.5\ny = 2.0\n");
+    assert_eq!(from_struct, from_map);
+}
+
+#[test]
+fn values_and_keys() {
+    let value = json!({
+        "table": {
+            "nested": { "z": 1 },
+        },
+        "mixed": [1, { "b": "x", "a": [{}] }],
+        "quoted key": "it's \"quoted\"\n",
+        "empty": {},
+    });
+
+    let expected = "\
+mixed = [1, { a = [{}], b = \"x\" }]
+\"quoted key\" = \"it's \\\"quoted\\\"\\n\"
+
+[empty]
+
+[table.nested]
+z = 1
+";
+    assert_eq!(basic_toml::to_string_canonical(&value).unwrap(), expected);
+    basic_toml::from_str::<serde_json::Value>(expected).unwrap();
+}
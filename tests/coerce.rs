@@ -0,0 +1,156 @@
+#![allow(clippy::float_cmp)]
+
+use basic_toml::Options;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    port: u16,
+    debug: bool,
+    ratio: f64,
+    hosts: Vec<String>,
+    ports: Vec<u16>,
+    server: Server,
+}
+
+#[derive(Debug, Deserialize)]
+struct Server {
+    workers: Option<u8>,
+    tags: Vec<Vec<String>>,
+}
+
+const TOML: &str = r#"
+port = " 8080 "
+debug = "false"
+ratio = "0.5"
+hosts = "localhost"
+ports = ["80", 443]
+
+[server]
+workers = "4"
+tags = "a"
+"#;
+
+#[test]
+fn coerce() {
+    let config: Config = Options::new().coerce(true).from_str(TOML).unwrap();
+    assert_eq!(config.port, 8080);
+    assert!(!config.debug);
+    assert_eq!(config.ratio, 0.5);
+    assert_eq!(config.hosts, ["localhost"]);
+    assert_eq!(config.ports, [80, 443]);
+    assert_eq!(config.server.workers, Some(4));
+    assert_eq!(config.server.tags, [["a"]]);
+}
+
+#[test]
+fn strict_by_default() {
+    let err = basic_toml::from_str::<Config>(TOML).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \" 8080 \", expected u16 for key `port` at line 2 column 8",
+    );
+}
+
+#[test]
+fn not_parseable() {
+    #[derive(Debug, Deserialize)]
+    struct Port {
+        #[allow(dead_code)]
+        port: u16,
+    }
+
+    let options = Options::new().coerce(true);
+    let err = options.from_str::<Port>("port = \"http\"").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"http\", expected u16 for key `port` at line 1 column 8",
+    );
+    let err = options.from_str::<Port>("port = \"70000\"").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid value: integer `70000`, expected u16 for key `port` at line 1 column 8",
+    );
+}
+
+#[test]
+fn toml_number_syntax() {
+    #[derive(Debug, Deserialize)]
+    struct Numbers {
+        int: Vec<i64>,
+        float: Vec<f64>,
+    }
+
+    let options = Options::new().coerce(true);
+    let toml = r#"
+        int = ["1_000", "0x10", "0o17", "0b11", "+5", "-3"]
+        float = ["1.5", "2e3", "-inf", "7"]
+    "#;
+    let numbers: Numbers = options.from_str(toml).unwrap();
+    assert_eq!(numbers.int, [1000, 16, 15, 3, 5, -3]);
+    assert_eq!(numbers.float, [1.5, 2000.0, f64::NEG_INFINITY, 7.0]);
+
+    for s in ["infinity", "NaN", "1.", "01", "1__0", "\"1\"", "1 2"] {
+        let toml = "float = [".to_owned() + &serde_json::to_string(s).unwrap() + "]\nint = []";
+        let err = options.from_str::<Numbers>(&toml).unwrap_err();
+        assert!(
+            err.to_string().starts_with("invalid type: string"),
+            "{}",
+            err
+        );
+    }
+    let err = options
+        .from_str::<Numbers>("int = [\"1.5\"]\nfloat = []")
+        .unwrap_err();
+    assert!(
+        err.to_string().starts_with("invalid type: string"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn strings_stay_strings() {
+    #[derive(Debug, Deserialize)]
+    struct Name {
+        name: String,
+        value: serde_json::Value,
+    }
+
+    let options = Options::new().coerce(true);
+    let name: Name = options.from_str("name = \"1\"\nvalue = \"true\"").unwrap();
+    assert_eq!(name.name, "1");
+    assert_eq!(name.value, "true");
+}
+
+#[test]
+fn other_entry_points() {
+    #[derive(Debug, Deserialize)]
+    struct Port {
+        port: u16,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Raw {
+        port: basic_toml::RawValue,
+    }
+
+    let options = Options::new().coerce(true);
+    let port: Port = options.from_value_str("{ port = \"80\" }").unwrap();
+    assert_eq!(port.port, 80);
+
+    let port: Option<Port> = options
+        .from_front_matter("+++\nport = \"80\"\n+++\n")
+        .unwrap();
+    assert_eq!(port.unwrap().port, 80);
+
+    std::env::set_var("COERCE__PORT", "'80'");
+    let port: Port = options
+        .from_str_with_env("port = 1", "COERCE", "__")
+        .unwrap();
+    assert_eq!(port.port, 80);
+
+    let raw: Raw = basic_toml::from_str("port = \"80\"").unwrap();
+    assert!(raw.port.deserialize_into::<u16>().is_err());
+    assert_eq!(options.deserialize_raw::<u16>(&raw.port).unwrap(), 80);
+}
@@ -0,0 +1,200 @@
+use basic_toml::style::{Commented, Inline};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    name: Commented<String>,
+    missing: Commented<Option<String>>,
+    tags: Vec<Commented<String>>,
+    server: Commented<Server>,
+    nested: Commented<Nested>,
+    users: Commented<Vec<Commented<User>>>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Server {
+    host: Commented<String>,
+    port: u16,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Nested {
+    inner: Server,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct User {
+    name: String,
+}
+
+fn uncommented<T>(value: T) -> Commented<T> {
+    Commented::new("", value)
+}
+
+#[test]
+fn comments() {
+    let config = Config {
+        name: Commented::new("The name.", "demo".to_owned()),
+        missing: Commented::new("Not written.", None),
+        tags: vec![Commented::new("Not written either.", "a".to_owned())],
+        server: Commented::new(
+            "The server.\n\nSecond paragraph.",
+            Server {
+                host: Commented::new("Host", "localhost".to_owned()),
+                port: 80,
+            },
+        ),
+        nested: Commented::new(
+            "Only holds tables.",
+            Nested {
+                inner: Server {
+                    host: uncommented("example.com".to_owned()),
+                    port: 443,
+                },
+            },
+        ),
+        users: Commented::new(
+            "Users.",
+            vec![
+                Commented::new(
+                    "First user.",
+                    User {
+                        name: "alice".to_owned(),
+                    },
+                ),
+                uncommented(User {
+                    name: "bob".to_owned(),
+                }),
+            ],
+        ),
+    };
+    let config = Commented::new("Generated, do not edit.", config);
+
+    let expected = "\
+# Generated, do not edit.
+
+# The name.
+name = \"demo\"
+tags = [\"a\"]
+
+# The server.
+#
+# Second paragraph.
+[server]
+# Host
+host = \"localhost\"
+port = 80
+# Only holds tables.
+[nested.inner]
+host = \"example.com\"
+port = 443
+
+# Users.
+# First user.
+[[users]]
+name = \"alice\"
+
+[[users]]
+name = \"bob\"
+";
+    let toml = basic_toml::to_string(&config).unwrap();
+    assert_eq!(toml, expected);
+
+    let parsed: Config = basic_toml::from_str(&toml).unwrap();
+    assert_eq!(parsed.name.value, config.value.name.value);
+    assert_eq!(parsed.server.value.host.value, "localhost");
+    assert_eq!(
+        parsed.users.value[1].value,
+        config.value.users.value[1].value
+    );
+    assert!(parsed.name.comment.is_empty());
+}
+
+#[test]
+fn nested() {
+    #[derive(Serialize)]
+    struct Doc {
+        key: Commented<Commented<i32>>,
+    }
+
+    let doc = Doc {
+        key: Commented::new("Outer.", Commented::new("Inner.", 1)),
+    };
+    let toml = basic_toml::to_string(&doc).unwrap();
+    assert_eq!(toml, "# Outer.\n# Inner.\nkey = 1\n");
+}
+
+#[test]
+fn invalid_comment() {
+    #[derive(Serialize)]
+    struct Doc {
+        key: Commented<i32>,
+    }
+
+    let doc = Doc {
+        key: Commented::new("bell\u{7}", 1),
+    };
+    let err = basic_toml::to_string(&doc).unwrap_err();
+    assert_eq!(err.to_string(), "comment cannot contain '\\u{7}'");
+}
+
+#[test]
+fn other_serializers() {
+    let value = Commented::new("Numbers.", vec![1, 2]);
+    assert_eq!(
+        serde_json::to_string(&value).unwrap(),
+        r#"{"comment":"Numbers.","value":[1,2]}"#,
+    );
+}
+
+#[test]
+fn value_strings() {
+    #[derive(Serialize)]
+    struct Doc {
+        name: Commented<&'static str>,
+        nickname: Commented<Option<&'static str>>,
+    }
+
+    let value = vec![Commented::new("Ignored.", 1), Commented::new("", 2)];
+    assert_eq!(basic_toml::to_value_string(&value).unwrap(), "[1, 2]");
+
+    let doc = Doc {
+        name: Commented::new("Ignored.", "John"),
+        nickname: Commented::new("Ignored.", None),
+    };
+    assert_eq!(
+        basic_toml::to_value_string(&doc).unwrap(),
+        "{ name = \"John\" }",
+    );
+}
+
+#[test]
+fn inline_values() {
+    #[derive(Serialize)]
+    struct Doc {
+        hosts: Commented<Vec<&'static str>>,
+        ports: Commented<Vec<Vec<u16>>>,
+        server: Commented<Inline<Server>>,
+    }
+
+    let doc = Doc {
+        hosts: Commented::new("Hosts.", vec!["a", "b"]),
+        ports: Commented::new("Ports.", vec![vec![80], vec![]]),
+        server: Commented::new(
+            "Inline.",
+            Inline(Server {
+                host: Commented::new("Not written.", "localhost".to_owned()),
+                port: 80,
+            }),
+        ),
+    };
+    let expected = "\
+# Hosts.
+hosts = [\"a\", \"b\"]
+# Ports.
+ports = [[80], []]
+# Inline.
+server = { host = \"localhost\", port = 80 }
+";
+    assert_eq!(basic_toml::to_string(&doc).unwrap(), expected);
+}
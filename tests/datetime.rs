@@ -40,7 +40,7 @@ fn times() {
         "foo = 1997-09-09 # comment",
         "invalid number at line 1 column 7"
     );
-    multi_bad("foo = 09:09:09", "invalid number at line 1 column 8");
+    multi_bad("foo = 09:09:09", "invalid number at line 1 column 7");
     multi_bad(
         "foo = 1997-09-09T09:09:09.09Z",
         "invalid number at line 1 column 7",
@@ -57,7 +57,7 @@ fn times() {
         "foo = 1997-09-09T09:09:09.09",
         "invalid number at line 1 column 7",
     );
-    multi_bad("foo = 09:09:09.09", "invalid number at line 1 column 8");
+    multi_bad("foo = 09:09:09.09", "invalid number at line 1 column 7");
 }
 
 #[test]
@@ -0,0 +1,142 @@
+use basic_toml::{DuplicateKeys, Options};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+const TOML: &str = r#"
+name = "a"
+version = 1
+name = "b"
+
+[dependencies]
+serde = "1.0"
+serde = { version = "1.0", features = ["derive"] }
+
+[table]
+inline = { x = 1, y = 2, x = 3 }
+dotted.x = 1
+dotted.x = 2
+"#;
+
+type Warnings = Arc<Mutex<Vec<(String, Range<usize>, Range<usize>)>>>;
+
+fn options(duplicate_keys: DuplicateKeys) -> (Options, Warnings) {
+    let warnings = Warnings::default();
+    let options = Options::new()
+        .duplicate_keys(duplicate_keys)
+        .on_duplicate_key({
+            let warnings = Arc::clone(&warnings);
+            move |dup| {
+                let warning = (dup.key().to_owned(), dup.first(), dup.second());
+                warnings.lock().unwrap().push(warning);
+            }
+        });
+    (options, warnings)
+}
+
+// The two occurrences of `key`, as the last part of the first two occurrences
+// of `pattern`, reported under the full path of the key.
+fn warning(pattern: &str, key: &str, path: &str) -> (String, Range<usize>, Range<usize>) {
+    let offset = pattern.rfind(key).unwrap();
+    let first = TOML.find(pattern).unwrap();
+    let second = first + 1 + TOML[first + 1..].find(pattern).unwrap();
+    let (first, second) = (first + offset, second + offset);
+    (
+        path.to_owned(),
+        first..first + key.len(),
+        second..second + key.len(),
+    )
+}
+
+#[test]
+fn error() {
+    let err = basic_toml::from_str::<Value>(TOML).unwrap_err();
+    assert_eq!(err.to_string(), "duplicate key: `name` at line 4 column 1");
+
+    let (options, warnings) = options(DuplicateKeys::Error);
+    let err = options.from_str::<Value>(TOML).unwrap_err();
+    assert_eq!(err.to_string(), "duplicate key: `name` at line 4 column 1");
+    assert!(warnings.lock().unwrap().is_empty());
+}
+
+#[test]
+fn last_wins() {
+    let (options, warnings) = options(DuplicateKeys::LastWins);
+    let value: Value = options.from_str(TOML).unwrap();
+    let expected = json!({
+        "name": "b",
+        "version": 1,
+        "dependencies": {
+            "serde": { "version": "1.0", "features": ["derive"] },
+        },
+        "table": {
+            "inline": { "x": 3, "y": 2 },
+            "dotted": { "x": 2 },
+        },
+    });
+    assert_eq!(value, expected);
+    assert_eq!(warnings.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn first_wins() {
+    let (options, warnings) = options(DuplicateKeys::FirstWins);
+    let value: Value = options.from_str(TOML).unwrap();
+    let expected = json!({
+        "name": "a",
+        "version": 1,
+        "dependencies": {
+            "serde": "1.0",
+        },
+        "table": {
+            "inline": { "x": 1, "y": 2 },
+            "dotted": { "x": 1 },
+        },
+    });
+    assert_eq!(value, expected);
+
+    let expected = [
+        warning("name", "name", "name"),
+        warning("serde", "serde", "dependencies.serde"),
+        warning("x =", "x", "table.inline.x"),
+        warning("dotted.x", "x", "table.dotted.x"),
+    ];
+    assert_eq!(*warnings.lock().unwrap(), expected);
+}
+
+#[test]
+fn nested_paths() {
+    let toml = "a.b = 1\na.b = 2\n[[c]]\nd = { e.f = [{ g = 1, g = 2 }] }\n";
+    let (options, warnings) = options(DuplicateKeys::LastWins);
+    let _: Value = options.from_str(toml).unwrap();
+    let keys: Vec<String> = warnings.lock().unwrap().drain(..).map(|w| w.0).collect();
+    assert_eq!(keys, ["a.b", "c.d.e.f.g"]);
+}
+
+#[test]
+fn struct_fields() {
+    #[derive(Deserialize)]
+    struct Package {
+        name: String,
+    }
+
+    let toml = "name = \"a\"\nname = \"b\"\n";
+    let options = Options::new().duplicate_keys(DuplicateKeys::LastWins);
+    let package: Package = options.from_str(toml).unwrap();
+    assert_eq!(package.name, "b");
+}
+
+#[test]
+fn tables_stay_errors() {
+    let options = Options::new().duplicate_keys(DuplicateKeys::LastWins);
+
+    let err = options.from_str::<Value>("[a]\n[a]\n").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "redefinition of table `a` for key `a` at line 2 column 1"
+    );
+
+    let err = options.from_str::<Value>("a = 1\n[a]\n").unwrap_err();
+    assert_eq!(err.to_string(), "duplicate key: `a` at line 2 column 2");
+}
@@ -28,3 +28,105 @@ fn from_str() {
 
     assert_eq!(Enum::Variant, s.value);
 }
+
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum Storage {
+    Local,
+    Path(String),
+    Pair(u8, u8),
+    S3 { bucket: String },
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Config {
+    storage: Storage,
+}
+
+fn s3() -> Storage {
+    Storage::S3 {
+        bucket: "b".to_owned(),
+    }
+}
+
+#[test]
+fn table_variants() {
+    for toml in [
+        "[storage.s3]\nbucket = \"b\"",
+        "[storage]\ns3.bucket = \"b\"",
+        "[storage]\n[storage.s3]\nbucket = \"b\"",
+        "storage.s3.bucket = \"b\"",
+        "storage = { s3 = { bucket = \"b\" } }",
+    ] {
+        let config: Config = basic_toml::from_str(toml).unwrap();
+        assert_eq!(config.storage, s3());
+    }
+
+    let config: Config = basic_toml::from_str("[storage.local]").unwrap();
+    assert_eq!(config.storage, Storage::Local);
+    let config: Config = basic_toml::from_str("storage = { local = {} }").unwrap();
+    assert_eq!(config.storage, Storage::Local);
+    let config: Config = basic_toml::from_str("[storage]\npath = \"/tmp\"").unwrap();
+    assert_eq!(config.storage, Storage::Path("/tmp".to_owned()));
+    let config: Config = basic_toml::from_str("[storage]\npair = [1, 2]").unwrap();
+    assert_eq!(config.storage, Storage::Pair(1, 2));
+}
+
+#[test]
+fn top_level() {
+    let storage: Storage = basic_toml::from_str("[s3]\nbucket = \"b\"").unwrap();
+    assert_eq!(storage, s3());
+    let storage: Storage = basic_toml::from_str("path = \"/tmp\"").unwrap();
+    assert_eq!(storage, Storage::Path("/tmp".to_owned()));
+}
+
+#[test]
+fn not_one_key() {
+    let error = basic_toml::from_str::<Config>("[storage]").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected exactly 1 element, found zero elements for key `storage` at line 1 column 1",
+    );
+
+    let error = basic_toml::from_str::<Config>("[storage]\nlocal = {}\npath = \"\"").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected exactly 1 element, found more than 1 element for key `storage` at line 3 column 1",
+    );
+
+    let toml = "[storage.local]\n\n[storage.s3]\nbucket = \"b\"\n";
+    let error = basic_toml::from_str::<Config>(toml).unwrap_err();
+    assert_eq!(error.line_col(), Some((2, 0)));
+    assert_eq!(
+        error.to_string(),
+        "expected exactly 1 element, found more than 1 element for key `storage` at line 3 column 1",
+    );
+
+    let error =
+        basic_toml::from_str::<Config>("storage = { local = {}, path = \"\" }").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected exactly 1 element, found more than 1 element for key `storage` at line 1 column 11",
+    );
+
+    let error = basic_toml::from_str::<Config>("storage = 1").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "expected string or table, found integer for key `storage` at line 1 column 11",
+    );
+}
+
+#[test]
+fn wrong_content() {
+    let error = basic_toml::from_str::<Config>("[storage.local]\na = 1").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid type: map, expected unit for key `storage.local` at line 1 column 1",
+    );
+
+    let error = basic_toml::from_str::<Config>("[storage.s3]\nbucket = 1").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "invalid type: integer `1`, expected a string for key `storage.s3.bucket` at line 2 column 10",
+    );
+}
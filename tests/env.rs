@@ -0,0 +1,109 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Config {
+    name: String,
+    server: Server,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Server {
+    port: u16,
+    hosts: Vec<String>,
+    tls: Option<Tls>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Tls {
+    cert: String,
+}
+
+const TOML: &str = r#"
+name = "app"
+
+[server]
+port = 80
+hosts = ["a"]
+"#;
+
+// All in one test because the environment is shared between threads.
+#[test]
+fn env_overrides() {
+    env::set_var("ENVTEST__SERVER__PORT", "9000");
+    env::set_var("ENVTEST__SERVER__HOSTS", "['b', 'c']");
+    env::set_var("ENVTEST__SERVER__TLS__CERT", "\"cert.pem\"");
+    env::set_var("ENVTESTX__NAME", "ignored");
+    let config: Config = basic_toml::from_str_with_env(TOML, "ENVTEST", "__").unwrap();
+    assert_eq!(
+        config,
+        Config {
+            name: "app".to_owned(),
+            server: Server {
+                port: 9000,
+                hosts: vec!["b".to_owned(), "c".to_owned()],
+                tls: Some(Tls {
+                    cert: "cert.pem".to_owned(),
+                }),
+            },
+        },
+    );
+
+    env::set_var("ENVBAD__NAME", "app");
+    let err = basic_toml::from_str_with_env::<Config>(TOML, "ENVBAD", "__").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid TOML value, did you mean to use a quoted string? in environment variable `ENVBAD__NAME`",
+    );
+
+    env::set_var("ENVTYPE__SERVER__PORT", "'80'");
+    let err = basic_toml::from_str_with_env::<Config>(TOML, "ENVTYPE", "__").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"80\", expected u16 for key `server.port` in environment variable `ENVTYPE__SERVER__PORT`",
+    );
+    assert_eq!(err.line_col(), None);
+
+    env::set_var("ENVDOTTED__NAME__FIRST", "'x'");
+    let err = basic_toml::from_str_with_env::<Config>(TOML, "ENVDOTTED", "__").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "dotted key attempted to extend non-table type in environment variable `ENVDOTTED__NAME__FIRST`",
+    );
+
+    // A table that only exists as the parent of `[server.tls]`.
+    let toml = "name = \"app\"\n\n[server.tls]\ncert = \"cert.pem\"\n";
+    env::set_var("ENVIMPLICIT__SERVER__PORT", "9000");
+    env::set_var("ENVIMPLICIT__SERVER__HOSTS", "[]");
+    let config: Config = basic_toml::from_str_with_env(toml, "ENVIMPLICIT", "__").unwrap();
+    assert_eq!(config.server.port, 9000);
+    assert_eq!(config.server.tls.unwrap().cert, "cert.pem");
+
+    env::set_var("ENVIMPLICITTYPE__SERVER__PORT", "'80'");
+    env::set_var("ENVIMPLICITTYPE__SERVER__HOSTS", "[]");
+    let err = basic_toml::from_str_with_env::<Config>(toml, "ENVIMPLICITTYPE", "__").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"80\", expected u16 for key `server.port` in environment variable `ENVIMPLICITTYPE__SERVER__PORT`",
+    );
+
+    // The last element of an array of tables.
+    let toml = "[[a]]\nx = 1\n\n[a.sub]\ny = 1\n\n[[a]]\nx = 2\n\n[a.sub]\ny = 2\n";
+    env::set_var("ENVARRAY__A__B", "3");
+    env::set_var("ENVARRAY__A__SUB", "{ z = 3 }");
+    let value: Value = basic_toml::from_str_with_env(toml, "ENVARRAY", "__").unwrap();
+    let expected = json!({
+        "a": [
+            { "x": 1, "sub": { "y": 1 } },
+            { "x": 2, "b": 3, "sub": { "z": 3 } },
+        ],
+    });
+    assert_eq!(value, expected);
+
+    // A whole table given by a header.
+    env::set_var("ENVTABLE__SERVER", "{ port = 1, hosts = [] }");
+    let config: Config = basic_toml::from_str_with_env(TOML, "ENVTABLE", "__").unwrap();
+    assert_eq!(config.server.port, 1);
+    assert!(config.server.hosts.is_empty());
+}
@@ -48,7 +48,7 @@ macro_rules! float_inf_tests {
         assert!(inf.sf5.is_nan());
         assert!(inf.sf5.is_sign_positive());
         assert!(inf.sf6.is_nan());
-        assert!(inf.sf6.is_sign_negative()); // NOTE: but serializes to just `nan`
+        assert!(inf.sf6.is_sign_negative());
 
         assert_eq!(inf.sf7, 0.0);
         assert!(inf.sf7.is_sign_positive());
@@ -64,7 +64,7 @@ sf2 = inf
 sf3 = -inf
 sf4 = nan
 sf5 = nan
-sf6 = nan
+sf6 = -nan
 sf7 = 0.0
 sf8 = -0.0
 "
@@ -79,3 +79,125 @@ fn float_inf() {
     float_inf_tests!(f32);
     float_inf_tests!(f64);
 }
+
+// Small xorshift generator, so that the tests below are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+macro_rules! float_roundtrip_tests {
+    ($ty:ty, $bits:ty, $special:expr) => {{
+        #[derive(Serialize, Deserialize)]
+        struct S {
+            x: $ty,
+        }
+
+        let check = |x: $ty| {
+            let s = basic_toml::to_string(&S { x }).unwrap();
+            let y = basic_toml::from_str::<S>(&s).unwrap().x;
+            if x.is_nan() {
+                // TOML can't represent the payload of a NaN, only its sign.
+                assert!(y.is_nan(), "{} -> {}", s.trim_end(), y);
+                assert_eq!(
+                    x.is_sign_negative(),
+                    y.is_sign_negative(),
+                    "{}",
+                    s.trim_end()
+                );
+            } else {
+                assert_eq!(x.to_bits(), y.to_bits(), "{} -> {:e}", s.trim_end(), y);
+            }
+            s
+        };
+
+        for x in $special {
+            check(x);
+        }
+
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..if cfg!(miri) { 100 } else { 100_000 } {
+            #[allow(clippy::cast_possible_truncation)]
+            let bits = rng.next() as $bits;
+            check(<$ty>::from_bits(bits));
+        }
+    }};
+}
+
+#[test]
+fn float_roundtrip_f64() {
+    float_roundtrip_tests!(
+        f64,
+        u64,
+        [
+            0.0,
+            -0.0,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::NAN,
+            -f64::NAN,
+            f64::MIN,
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            f64::EPSILON,
+            f64::from_bits(1),
+            -f64::from_bits(1),
+            f64::from_bits(0x000f_ffff_ffff_ffff),
+            0.1,
+            1e15,
+            1e16,
+            1e-4,
+            1e-5,
+            123_456_789.012_345_68,
+        ]
+    );
+}
+
+#[test]
+fn float_roundtrip_f32() {
+    float_roundtrip_tests!(
+        f32,
+        u32,
+        [
+            0.0,
+            -0.0,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+            -f32::NAN,
+            f32::MIN,
+            f32::MAX,
+            f32::MIN_POSITIVE,
+            f32::EPSILON,
+            f32::from_bits(1),
+            -f32::from_bits(1),
+            0.1,
+            16_777_217.0,
+        ]
+    );
+}
+
+#[test]
+fn float_format() {
+    #[derive(Serialize)]
+    struct S {
+        x: f64,
+    }
+
+    let format = |x| basic_toml::to_string(&S { x }).unwrap();
+    assert_eq!(format(1.0), "x = 1.0\n");
+    assert_eq!(format(0.1), "x = 0.1\n");
+    assert_eq!(format(1e15), "x = 1000000000000000.0\n");
+    assert_eq!(format(1e16), "x = 1e16\n");
+    assert_eq!(format(1.5e300), "x = 1.5e300\n");
+    assert_eq!(format(0.0001), "x = 0.0001\n");
+    assert_eq!(format(0.000_012_5), "x = 1.25e-5\n");
+    assert_eq!(format(f64::from_bits(1)), "x = 5e-324\n");
+    assert_eq!(format(-f64::NAN), "x = -nan\n");
+}
@@ -51,3 +51,143 @@ fn no_unnecessary_newlines_table() {
     .unwrap()
     .starts_with('\n'));
 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Groups {
+    pub groups: Vec<Vec<User>>,
+}
+
+#[test]
+fn nested_arrays_of_tables() {
+    let groups = Groups {
+        groups: vec![
+            vec![User {
+                name: "John".to_string(),
+                surname: "Doe".to_string(),
+            }],
+            vec![],
+        ],
+    };
+    let toml = to_string(&groups).unwrap();
+    assert_eq!(
+        toml,
+        "groups = [[{ name = \"John\", surname = \"Doe\" }], []]\n",
+    );
+    assert_eq!(basic_toml::from_str::<Groups>(&toml).unwrap(), groups);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    Name(String),
+    User(User),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Entries {
+    pub entries: Vec<Entry>,
+    pub table: Users,
+}
+
+#[test]
+fn mixed_tables_and_scalars() {
+    let entries = Entries {
+        entries: vec![
+            Entry::Name("root".to_string()),
+            Entry::User(User {
+                name: "Jane".to_string(),
+                surname: "Dough".to_string(),
+            }),
+        ],
+        table: Users {
+            user: vec![User {
+                name: "John".to_string(),
+                surname: "Doe".to_string(),
+            }],
+        },
+    };
+    let toml = to_string(&entries).unwrap();
+    assert_eq!(
+        toml,
+        "\
+entries = [\"root\", { name = \"Jane\", surname = \"Dough\" }]
+[[table.user]]
+name = \"John\"
+surname = \"Doe\"
+",
+    );
+    assert_eq!(basic_toml::from_str::<Entries>(&toml).unwrap(), entries);
+}
+
+#[test]
+fn tables_nested_in_inline_arrays() {
+    let value = serde_json::json!({
+        "points": [[1, {"x": 2, "y": {"z": [3]}}], {}],
+    });
+    let toml = to_string(&value).unwrap();
+    assert_eq!(toml, "points = [[1, { x = 2, y = { z = [3] } }], {}]\n");
+    assert_eq!(
+        basic_toml::from_str::<serde_json::Value>(&toml).unwrap(),
+        value,
+    );
+}
+
+// Counts how many times it is serialized.
+struct Counted<T> {
+    count: std::cell::Cell<usize>,
+    value: T,
+}
+
+impl<T: Serialize> Serialize for Counted<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.count.set(self.count.get() + 1);
+        self.value.serialize(serializer)
+    }
+}
+
+fn counted<T>(value: T) -> Counted<T> {
+    Counted {
+        count: std::cell::Cell::new(0),
+        value,
+    }
+}
+
+#[test]
+fn serialized_once() {
+    #[derive(Serialize)]
+    struct Outer<'a> {
+        entries: &'a Counted<Vec<Entry>>,
+        users: &'a Counted<Vec<&'a Counted<User>>>,
+        table: &'a Counted<Users>,
+    }
+
+    let user = counted(User {
+        name: "John".to_string(),
+        surname: "Doe".to_string(),
+    });
+    let users = counted(vec![&user]);
+    let entries = counted(vec![Entry::Name("root".to_string())]);
+    let table = counted(Users { user: Vec::new() });
+    let outer = Outer {
+        entries: &entries,
+        users: &users,
+        table: &table,
+    };
+    let toml = to_string(&outer).unwrap();
+    assert_eq!(
+        toml,
+        "\
+entries = [\"root\"]
+
+[[users]]
+name = \"John\"
+surname = \"Doe\"
+
+[table]
+user = []
+",
+    );
+    for count in [&user.count, &users.count, &entries.count, &table.count] {
+        assert_eq!(count.get(), 1);
+    }
+}
@@ -0,0 +1,150 @@
+use basic_toml::Options;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Config {
+    name: String,
+    server: Server,
+    peers: Vec<Peer>,
+    limits: BTreeMap<String, u32>,
+    mode: Mode,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Server {
+    host: String,
+    port: u16,
+    timeout: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Peer {
+    ip: String,
+    port: u16,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+enum Mode {
+    Fixed(u32),
+    Range(u32, u32),
+    Named { name: String },
+}
+
+fn initial() -> Config {
+    Config {
+        name: "app".to_owned(),
+        server: Server {
+            host: "localhost".to_owned(),
+            port: 80,
+            timeout: None,
+        },
+        peers: vec![Peer {
+            ip: "10.0.0.1".to_owned(),
+            port: 8080,
+        }],
+        limits: BTreeMap::from([("a".to_owned(), 1), ("b".to_owned(), 2)]),
+        mode: Mode::Fixed(1),
+    }
+}
+
+#[test]
+fn partial_update() {
+    let mut config = initial();
+    basic_toml::from_str_into(
+        "
+        limits.b = 3
+
+        [server]
+        port = 8000
+        timeout = 30
+        ",
+        &mut config,
+    )
+    .unwrap();
+
+    let mut expected = initial();
+    expected.server.port = 8000;
+    expected.server.timeout = Some(30);
+    expected.limits.insert("b".to_owned(), 3);
+    assert_eq!(config, expected);
+}
+
+#[test]
+fn replace_array_of_tables() {
+    let mut config = initial();
+    basic_toml::from_str_into(
+        r#"
+        [[peers]]
+        ip = "10.0.0.2"
+        port = 1
+
+        [[peers]]
+        ip = "10.0.0.3"
+        port = 2
+        "#,
+        &mut config,
+    )
+    .unwrap();
+
+    let mut expected = initial();
+    expected.peers = vec![
+        Peer {
+            ip: "10.0.0.2".to_owned(),
+            port: 1,
+        },
+        Peer {
+            ip: "10.0.0.3".to_owned(),
+            port: 2,
+        },
+    ];
+    assert_eq!(config, expected);
+}
+
+#[test]
+fn enum_variants() {
+    let mut config = initial();
+    basic_toml::from_str_into("name = \"x\"", &mut config).unwrap();
+    assert_eq!(config.mode, Mode::Fixed(1));
+
+    basic_toml::from_str_into("mode = { Range = [1, 5] }", &mut config).unwrap();
+    assert_eq!(config.mode, Mode::Range(1, 5));
+
+    let toml = "[mode.Named]\nname = \"y\"\n";
+    basic_toml::from_str_into(toml, &mut config).unwrap();
+    let name = "y".to_owned();
+    assert_eq!(config.mode, Mode::Named { name });
+
+    basic_toml::from_str_into("[server]\nport = 1\n", &mut config).unwrap();
+    let name = "y".to_owned();
+    assert_eq!(config.mode, Mode::Named { name });
+    assert_eq!(config.name, "x");
+}
+
+#[test]
+fn errors() {
+    let mut config = initial();
+    let toml = "name = \"new\"\n\n[server]\nport = 'x'\n";
+    let err = basic_toml::from_str_into(toml, &mut config).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"x\", expected u16 for key `server.port` at line 4 column 8",
+    );
+    assert_eq!(config.server, initial().server);
+
+    let err = basic_toml::from_str_into("name = 'a'\nname = 'b'\n", &mut config).unwrap_err();
+    assert_eq!(err.to_string(), "duplicate key: `name` at line 2 column 1");
+    assert_eq!(config.peers, initial().peers);
+}
+
+#[test]
+fn options() {
+    let mut config = initial();
+    let toml = "[server]\nport = '8000'\n";
+    assert!(basic_toml::from_str_into(toml, &mut config).is_err());
+
+    let options = Options::new().coerce(true);
+    options.from_str_into(toml, &mut config).unwrap();
+    assert_eq!(config.server.port, 8000);
+    assert_eq!(config.server.host, "localhost");
+}
@@ -0,0 +1,107 @@
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+struct Page {
+    title: String,
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    dependencies: serde_json::Value,
+}
+
+#[test]
+fn markdown() {
+    let markdown = "\
++++
+title = \"Hello\"
+tags = [\"a\", \"b\"]
++++
+
+# Hello
+";
+    assert_eq!(
+        basic_toml::front_matter(markdown),
+        Some("title = \"Hello\"\ntags = [\"a\", \"b\"]\n"),
+    );
+    let page: Page = basic_toml::from_front_matter(markdown).unwrap().unwrap();
+    assert_eq!(page.title, "Hello");
+    assert_eq!(page.tags, ["a", "b"]);
+}
+
+#[test]
+fn cargo_script() {
+    let script = "\
+#!/usr/bin/env cargo
+
+----cargo
+[dependencies]
+regex = \"1\"
+
+[package]
+description = \"\"\"
+---
+\"\"\"
+----
+
+fn main() {}
+";
+    let manifest: Manifest = basic_toml::from_front_matter(script).unwrap().unwrap();
+    assert_eq!(manifest.dependencies["regex"], "1");
+    assert!(basic_toml::front_matter(script)
+        .unwrap()
+        .ends_with("---\n\"\"\"\n"));
+}
+
+#[test]
+fn empty_infostring() {
+    let script = "#!/usr/bin/env cargo\n---\n[dependencies]\nregex = \"1\"\n---\n\nfn main() {}\n";
+    assert_eq!(
+        basic_toml::front_matter(script),
+        Some("[dependencies]\nregex = \"1\"\n"),
+    );
+    let manifest: Manifest = basic_toml::from_front_matter(script).unwrap().unwrap();
+    assert_eq!(manifest.dependencies["regex"], "1");
+}
+
+#[test]
+fn crlf() {
+    let markdown = "+++\r\ntitle = \"Hi\"\r\ntags = []\r\n+++\r\n";
+    let page: Page = basic_toml::from_front_matter(markdown).unwrap().unwrap();
+    assert_eq!(page.title, "Hi");
+}
+
+#[test]
+fn none() {
+    for s in [
+        "",
+        "title = \"Hello\"\n",
+        "+++\ntitle = \"Hello\"\n",
+        "#![allow(dead_code)]\n---cargo\n---\n",
+        "fn main() {}\n---cargo\n---\n",
+    ] {
+        assert_eq!(basic_toml::front_matter(s), None);
+        assert!(basic_toml::from_front_matter::<Page>(s).unwrap().is_none());
+    }
+}
+
+#[test]
+fn error_location() {
+    let markdown = "\
++++
+title = \"Hello\"
+tags = [1]
++++
+";
+    let err = basic_toml::from_front_matter::<Page>(markdown).unwrap_err();
+    assert_eq!(err.line_col(), Some((2, 8)));
+    assert_eq!(
+        err.to_string(),
+        "invalid type: integer `1`, expected a string for key `tags` at line 3 column 9",
+    );
+
+    let script = "#!/usr/bin/env cargo\n---cargo\n[dependencies\n---\n";
+    let err = basic_toml::from_front_matter::<Manifest>(script).unwrap_err();
+    assert_eq!(err.line_col(), Some((2, 13)));
+}
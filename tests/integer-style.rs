@@ -0,0 +1,122 @@
+use basic_toml::style::{Binary, Grouped, Hex, Octal};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Registers {
+    mode: Octal<u32>,
+    mask: Hex<u64>,
+    flags: Binary<u8>,
+    limit: Grouped<i64>,
+    wide: Hex<Grouped<u32>>,
+    bits: Grouped<Binary<u16>>,
+    list: Vec<Hex<u8>>,
+}
+
+#[test]
+fn styles() {
+    let registers = Registers {
+        mode: Octal(0o755),
+        mask: Hex(0xDEAD_BEEF),
+        flags: Binary(0b101),
+        limit: Grouped(-1_234_567),
+        wide: Hex(Grouped(0x1_FF00)),
+        bits: Grouped(Binary(0b1_0000_0001)),
+        list: vec![Hex(0), Hex(255)],
+    };
+    let expected = "\
+mode = 0o755
+mask = 0xDEADBEEF
+flags = 0b101
+limit = -1_234_567
+wide = 0x1_FF00
+bits = 0b1_0000_0001
+list = [0x0, 0xFF]
+";
+    let toml = basic_toml::to_string(&registers).unwrap();
+    assert_eq!(toml, expected);
+    assert_eq!(basic_toml::from_str::<Registers>(&toml).unwrap(), registers);
+}
+
+#[test]
+fn grouped_decimal() {
+    let to_string = |n: i64| {
+        let mut map = BTreeMap::new();
+        map.insert("n", Grouped(n));
+        let toml = basic_toml::to_string(&map).unwrap();
+        toml["n = ".len()..toml.len() - 1].to_owned()
+    };
+    assert_eq!(to_string(0), "0");
+    assert_eq!(to_string(999), "999");
+    assert_eq!(to_string(1000), "1_000");
+    assert_eq!(to_string(-100_000), "-100_000");
+    assert_eq!(to_string(i64::MIN), "-9_223_372_036_854_775_808");
+}
+
+#[test]
+fn negative() {
+    #[derive(Serialize)]
+    struct Negative {
+        n: Hex<i32>,
+    }
+
+    let err = basic_toml::to_string(&Negative { n: Hex(-1) }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "negative integer cannot be written with a 0x prefix: -1",
+    );
+}
+
+#[test]
+fn too_large() {
+    #[derive(Serialize)]
+    struct Large {
+        n: Hex<u64>,
+    }
+
+    let toml = basic_toml::to_string(&Large {
+        n: Hex(i64::MAX as u64),
+    })
+    .unwrap();
+    assert_eq!(toml, "n = 0x7FFFFFFFFFFFFFFF\n");
+
+    let err = basic_toml::to_string(&Large { n: Hex(u64::MAX) }).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "integer exceeds i64::MAX and cannot be written as a TOML integer: 18446744073709551615",
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("n", Grouped(u64::MAX));
+    assert!(basic_toml::to_string(&map).is_err());
+
+    // The same limit applies without a style.
+    let mut map = BTreeMap::new();
+    map.insert("n", i64::MAX as u64);
+    assert_eq!(
+        basic_toml::to_string(&map).unwrap(),
+        "n = 9223372036854775807\n"
+    );
+    map.insert("n", u64::MAX);
+    let err = basic_toml::to_string(&map).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "integer exceeds i64::MAX and cannot be written as a TOML integer: 18446744073709551615",
+    );
+    let err = basic_toml::to_value_string(&[u64::MAX]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "integer exceeds i64::MAX and cannot be written as a TOML integer: 18446744073709551615",
+    );
+}
+
+#[test]
+fn not_an_integer() {
+    #[derive(Serialize)]
+    struct Float {
+        f: Hex<f64>,
+    }
+
+    let toml = basic_toml::to_string(&Float { f: Hex(1.5) }).unwrap();
+    assert_eq!(toml, "f = 1.5\n");
+}
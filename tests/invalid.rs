@@ -14,6 +14,16 @@ macro_rules! test( ($name:ident, $s:expr, $msg:expr) => (
     fn $name() { bad!($s, $msg); }
 ) );
 
+test!(
+    comment_control,
+    include_str!("invalid/comment-control.toml"),
+    "unexpected character found: `\\u{1b}` at line 1 column 35"
+);
+test!(
+    comment_del,
+    include_str!("invalid/comment-del.toml"),
+    "unexpected character found: `\\u{7f}` at line 1 column 19"
+);
 test!(
     datetime_malformed_no_leads,
     include_str!("invalid/datetime-malformed-no-leads.toml"),
@@ -34,6 +44,26 @@ test!(
     include_str!("invalid/datetime-malformed-with-milli.toml"),
     "invalid number at line 1 column 14"
 );
+test!(
+    dotted_key_extend_table,
+    include_str!("invalid/dotted-key-extend-table.toml"),
+    "duplicate key: `b` for key `a` at line 5 column 1"
+);
+test!(
+    dotted_key_reopen_root_table,
+    include_str!("invalid/dotted-key-reopen-root-table.toml"),
+    "redefinition of table `a.b` at line 3 column 1"
+);
+test!(
+    dotted_key_reopen_table,
+    include_str!("invalid/dotted-key-reopen-table.toml"),
+    "duplicate key: `apple` for key `fruit` at line 4 column 8"
+);
+test!(
+    dotted_key_subtable_redefined,
+    include_str!("invalid/dotted-key-subtable-redefined.toml"),
+    "redefinition of table `fruit.apple.texture` at line 7 column 1"
+);
 test!(
     duplicate_key_table,
     include_str!("invalid/duplicate-key-table.toml"),
@@ -77,7 +107,27 @@ test!(
 test!(
     float_no_trailing_digits,
     include_str!("invalid/float-no-trailing-digits.toml"),
-    "invalid number at line 1 column 12"
+    "expected digits after the decimal point at line 1 column 11"
+);
+test!(
+    inline_table_extend_dotted,
+    include_str!("invalid/inline-table-extend-dotted.toml"),
+    "dotted key attempted to extend non-table type at line 1 column 5"
+);
+test!(
+    inline_table_extend_header,
+    include_str!("invalid/inline-table-extend-header.toml"),
+    "duplicate key: `type` for key `product` at line 4 column 10"
+);
+test!(
+    inline_table_extend_subtable,
+    include_str!("invalid/inline-table-extend-subtable.toml"),
+    "duplicate key: `a` at line 3 column 2"
+);
+test!(
+    integer_hex_plus,
+    include_str!("invalid/integer-hex-plus.toml"),
+    "only decimal numbers may have a sign at line 1 column 5"
 );
 test!(
     key_after_array,
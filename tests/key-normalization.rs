@@ -0,0 +1,138 @@
+use basic_toml::{KeyNormalization, Options};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Package {
+    rust_version: String,
+    default_features: bool,
+    build_dependencies: BTreeMap<String, String>,
+    resolver: Resolver,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Resolver {
+    LegacyStyle,
+    NewStyle,
+}
+
+#[test]
+fn snake_case() {
+    let toml = r#"
+        rust-version = "1.56"
+        default_features = false
+        resolver = "new-style"
+
+        [build-dependencies]
+        cc-rs = "1.0"
+    "#;
+
+    let options = Options::new().normalize_keys(KeyNormalization::SnakeCase);
+    let package: Package = options.from_str(toml).unwrap();
+    assert_eq!(package.rust_version, "1.56");
+    assert!(!package.default_features);
+    assert_eq!(package.resolver, Resolver::NewStyle);
+    assert_eq!(package.build_dependencies["cc_rs"], "1.0");
+
+    assert!(basic_toml::from_str::<Package>(toml).is_err());
+}
+
+#[test]
+fn kebab_case() {
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "kebab-case")]
+    struct Manifest {
+        rust_version: String,
+        inline: BTreeMap<String, i64>,
+    }
+
+    let toml = "rust_version = \"1.56\"\ninline = { max_size = 1, min-size = 0 }\n";
+    let options = Options::new().normalize_keys(KeyNormalization::KebabCase);
+    let manifest: Manifest = options.from_str(toml).unwrap();
+    assert_eq!(manifest.rust_version, "1.56");
+    assert_eq!(manifest.inline["max-size"], 1);
+    assert_eq!(manifest.inline["min-size"], 0);
+}
+
+#[test]
+fn lowercase() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Level {
+        Debug,
+        Info,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        level: Level,
+        levels: Vec<Level>,
+        output: Output,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Output {
+        File { path: String },
+    }
+
+    let toml = "LEVEL = \"Debug\"\nLevels = [\"INFO\"]\n[Output.File]\nPath = \"log\"\n";
+    let options = Options::new().normalize_keys(KeyNormalization::Lowercase);
+    let config: Config = options.from_str(toml).unwrap();
+    let expected = Config {
+        level: Level::Debug,
+        levels: vec![Level::Info],
+        output: Output::File {
+            path: "log".to_owned(),
+        },
+    };
+    assert_eq!(config, expected);
+
+    let toml = "level = \"debug\"\nlevels = []\noutput = { FILE = { PATH = \"log\" } }\n";
+    let config: Config = options.from_str(toml).unwrap();
+    assert_eq!(config.output, expected.output);
+}
+
+#[test]
+fn collision() {
+    let options = Options::new().normalize_keys(KeyNormalization::SnakeCase);
+
+    let toml = "rust-version = \"1.56\"\nrust_version = \"1.60\"\n";
+    let err = options.from_str::<Package>(toml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "keys `rust-version` and `rust_version` are both normalized to `rust_version` at line 2 column 1",
+    );
+
+    let toml = "[a-b]\nx = 1\n\n[a_b]\ny = 2\n";
+    let err = options
+        .from_str::<BTreeMap<String, BTreeMap<String, i64>>>(toml)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "keys `a-b` and `a_b` are both normalized to `a_b` at line 4 column 2",
+    );
+
+    let toml = "table = { a-b = 1, a_b = 2 }";
+    let err = options
+        .from_str::<BTreeMap<String, BTreeMap<String, i64>>>(toml)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "keys `a-b` and `a_b` are both normalized to `a_b` for key `table` at line 1 column 20",
+    );
+}
+
+#[test]
+fn duplicate() {
+    let options = Options::new().normalize_keys(KeyNormalization::SnakeCase);
+    let toml = "table = { a-b = 1, a-b = 2 }";
+    let err = options
+        .from_str::<BTreeMap<String, BTreeMap<String, i64>>>(toml)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "duplicate key: `a-b` for key `table` at line 1 column 20",
+    );
+}
@@ -0,0 +1,122 @@
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+struct Tool {
+    name: &'static str,
+    options: Options,
+    runs: Vec<Run>,
+}
+
+#[derive(Serialize)]
+struct Options {
+    verbose: bool,
+}
+
+#[derive(Serialize)]
+struct Run {
+    args: Vec<&'static str>,
+}
+
+fn tool() -> Tool {
+    Tool {
+        name: "demo",
+        options: Options { verbose: true },
+        runs: vec![Run { args: vec!["a"] }, Run { args: vec![] }],
+    }
+}
+
+#[test]
+fn table() {
+    let toml = basic_toml::to_string_at(&tool(), &["package", "metadata", "ourtool"]).unwrap();
+    let expected = "\
+[package.metadata.ourtool]
+name = \"demo\"
+
+[package.metadata.ourtool.options]
+verbose = true
+
+[[package.metadata.ourtool.runs]]
+args = [\"a\"]
+
+[[package.metadata.ourtool.runs]]
+args = []
+";
+    assert_eq!(toml, expected);
+
+    let document: serde_json::Value = basic_toml::from_str(&toml).unwrap();
+    let ourtool = &document["package"]["metadata"]["ourtool"];
+    assert_eq!(ourtool["name"], "demo");
+    assert_eq!(ourtool["runs"].as_array().unwrap().len(), 2);
+}
+
+#[test]
+fn empty_path() {
+    assert_eq!(
+        basic_toml::to_string_at(&tool(), &[]).unwrap(),
+        basic_toml::to_string(&tool()).unwrap(),
+    );
+}
+
+#[test]
+fn not_a_table() {
+    assert_eq!(
+        basic_toml::to_string_at(&5, &["a", "b"]).unwrap(),
+        "[a]\nb = 5\n",
+    );
+    assert_eq!(
+        basic_toml::to_string_at(&vec![1, 2], &["list"]).unwrap(),
+        "list = [1, 2]\n",
+    );
+    assert_eq!(
+        basic_toml::to_string_at(&BTreeMap::<String, i32>::new(), &["a", "b"]).unwrap(),
+        "[a.b]\n",
+    );
+}
+
+#[test]
+fn array_entry() {
+    #[derive(Serialize)]
+    struct Profile {
+        name: &'static str,
+        opt: Options,
+    }
+
+    let profile = Profile {
+        name: "release",
+        opt: Options { verbose: false },
+    };
+    let toml = basic_toml::to_string_array_entry_at(&profile, &["profile"]).unwrap();
+    let expected = "\
+[[profile]]
+name = \"release\"
+
+[profile.opt]
+verbose = false
+";
+    assert_eq!(toml, expected);
+
+    let toml = basic_toml::to_string_array_entry_at(&tool(), &["a", "b"]).unwrap();
+    let expected = "\
+[[a.b]]
+name = \"demo\"
+
+[a.b.options]
+verbose = true
+
+[[a.b.runs]]
+args = [\"a\"]
+
+[[a.b.runs]]
+args = []
+";
+    assert_eq!(toml, expected);
+
+    let err = basic_toml::to_string_array_entry_at(&5, &["a"]).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported Rust type");
+    let err = basic_toml::to_string_array_entry_at(&profile, &[]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "array of tables entry requires a non-empty key path",
+    );
+}
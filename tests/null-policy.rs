@@ -0,0 +1,226 @@
+#![allow(clippy::zero_sized_map_values)]
+
+use basic_toml::{NullPolicy, Options};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+struct Point {
+    x: Option<i32>,
+    y: i32,
+}
+
+#[derive(Serialize)]
+struct Document {
+    field: Option<i32>,
+    list: Vec<Option<i32>>,
+    tuple: (Option<&'static str>, i32),
+    points: Vec<Option<Point>>,
+    tables: Vec<Point>,
+}
+
+fn document() -> Document {
+    Document {
+        field: None,
+        list: vec![Some(1), None, Some(3)],
+        tuple: (None, 2),
+        points: vec![Some(Point { x: Some(1), y: 2 }), None],
+        tables: vec![Point { x: None, y: 4 }],
+    }
+}
+
+fn to_string<T: Serialize>(nulls: NullPolicy, value: &T) -> Result<String, String> {
+    let options = Options::new().null_policy(nulls);
+    options.to_string(value).map_err(|e| e.to_string())
+}
+
+#[test]
+fn default() {
+    let err = basic_toml::to_string(&document()).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported None value");
+
+    let point = Point { x: None, y: 1 };
+    assert_eq!(basic_toml::to_string(&point).unwrap(), "y = 1\n");
+    assert!(basic_toml::to_string(&None::<Point>).is_err());
+}
+
+#[test]
+fn skip() {
+    let expected = "\
+list = [1, 3]
+tuple = [2]
+points = [{ x = 1, y = 2 }]
+
+[[tables]]
+y = 4
+";
+    assert_eq!(to_string(NullPolicy::Skip, &document()).unwrap(), expected);
+    assert_eq!(to_string(NullPolicy::Skip, &None::<Point>).unwrap(), "");
+
+    let mut map = BTreeMap::new();
+    map.insert("a", vec![None::<i32>]);
+    map.insert("b", vec![Some(1)]);
+    assert_eq!(
+        to_string(NullPolicy::Skip, &map).unwrap(),
+        "a = []\nb = [1]\n",
+    );
+}
+
+#[test]
+fn error() {
+    let point = Point { x: None, y: 1 };
+    assert_eq!(
+        to_string(NullPolicy::Error, &point).unwrap_err(),
+        "unsupported None value",
+    );
+    assert_eq!(
+        to_string(NullPolicy::Error, &vec![None::<i32>]).unwrap_err(),
+        "unsupported None value",
+    );
+}
+
+#[test]
+fn placeholder() {
+    let expected = "\
+field = \"null\"
+list = [1, \"null\", 3]
+tuple = [\"null\", 2]
+points = [{ x = 1, y = 2 }, \"null\"]
+
+[[tables]]
+x = \"null\"
+y = 4
+";
+    let nulls = || NullPolicy::Placeholder("null".to_owned());
+    assert_eq!(to_string(nulls(), &document()).unwrap(), expected);
+    let mut map = BTreeMap::new();
+    map.insert("a", BTreeMap::from([("b", None::<i32>)]));
+    assert_eq!(to_string(nulls(), &map).unwrap(), "[a]\nb = \"null\"\n");
+}
+
+#[test]
+fn json_null() {
+    let value = json!({
+        "a": null,
+        "b": [null, 1],
+        "c": { "d": null, "e": 2 },
+        "b2": [1, { "n": { "o": null, "p": 5 } }],
+        "z": [{ "g": null, "h": 3 }],
+        "i": { "h": 4, "j": { "k": null } },
+    });
+    let expected = "\
+b = [1]
+b2 = [1, { n = { p = 5 } }]
+
+[c]
+e = 2
+
+[i]
+h = 4
+
+[i.j]
+
+[[z]]
+h = 3
+";
+    assert_eq!(to_string(NullPolicy::Skip, &value).unwrap(), expected);
+    assert_eq!(to_string(NullPolicy::Skip, &json!(null)).unwrap(), "");
+
+    let expected = "\
+a = \"-\"
+b = [\"-\", 1]
+b2 = [1, { n = { o = \"-\", p = 5 } }]
+
+[c]
+d = \"-\"
+e = 2
+
+[i]
+h = 4
+
+[i.j]
+k = \"-\"
+
+[[z]]
+g = \"-\"
+h = 3
+";
+    let nulls = NullPolicy::Placeholder("-".to_owned());
+    assert_eq!(to_string(nulls, &value).unwrap(), expected);
+
+    for value in [
+        json!(null),
+        json!({ "a": null }),
+        json!({ "b": [null, 1] }),
+        json!({ "b2": [1, { "n": { "o": null } }] }),
+        json!({ "z": [{ "g": null }] }),
+        json!({ "i": { "j": { "k": null } } }),
+    ] {
+        assert_eq!(
+            to_string(NullPolicy::Error, &value).unwrap_err(),
+            "unsupported None value",
+        );
+    }
+}
+
+#[test]
+fn unit_struct_is_not_null() {
+    #[derive(Serialize)]
+    struct Enabled;
+
+    let mut features = BTreeMap::new();
+    features.insert("a", Enabled);
+    features.insert("b", Enabled);
+    let expected = "a = {}\nb = {}\n";
+    for nulls in [
+        NullPolicy::Skip,
+        NullPolicy::Error,
+        NullPolicy::Placeholder("null".to_owned()),
+    ] {
+        assert_eq!(to_string(nulls, &features).unwrap(), expected);
+    }
+}
+
+#[test]
+fn other_entry_points() {
+    let point = Point { x: None, y: 1 };
+    let nulls = || NullPolicy::Placeholder("null".to_owned());
+    let options = Options::new().null_policy(nulls());
+    assert_eq!(
+        options.to_string_at(&point, &["a"]).unwrap(),
+        "[a]\nx = \"null\"\ny = 1\n",
+    );
+    assert_eq!(
+        options.to_string_array_entry_at(&point, &["a"]).unwrap(),
+        "[[a]]\nx = \"null\"\ny = 1\n",
+    );
+    assert_eq!(
+        options.to_value_string(&point).unwrap(),
+        "{ x = \"null\", y = 1 }",
+    );
+    assert_eq!(
+        options.to_string_canonical(&point).unwrap(),
+        "x = \"null\"\ny = 1\n",
+    );
+
+    let options = Options::new().null_policy(NullPolicy::Skip);
+    let list = vec![Some(1), None];
+    assert_eq!(options.to_value_string(&list).unwrap(), "[1]");
+    let expected = "\
+list = [1, 3]
+tuple = [2]
+
+[[points]]
+x = 1
+y = 2
+
+[[tables]]
+y = 4
+";
+    assert_eq!(options.to_string_canonical(&document()).unwrap(), expected);
+
+    let options = Options::new().null_policy(NullPolicy::Error);
+    assert!(options.to_string_at(&point, &["a"]).is_err());
+    assert!(options.to_string_canonical(&point).is_err());
+}
@@ -221,31 +221,130 @@ fn string_no_newline() {
 
 #[test]
 fn bad_leading_zeros() {
-    bad!("a = 00", "invalid number at line 1 column 6");
-    bad!("a = -00", "invalid number at line 1 column 7");
-    bad!("a = +00", "invalid number at line 1 column 7");
-    bad!("a = 00.0", "invalid number at line 1 column 6");
-    bad!("a = -00.0", "invalid number at line 1 column 7");
-    bad!("a = +00.0", "invalid number at line 1 column 7");
+    bad!(
+        "a = 00",
+        "leading zeros are not allowed in decimal integers at line 1 column 5"
+    );
+    bad!(
+        "a = -00",
+        "leading zeros are not allowed in decimal integers at line 1 column 6"
+    );
+    bad!(
+        "a = +00",
+        "leading zeros are not allowed in decimal integers at line 1 column 6"
+    );
+    bad!(
+        "a = 00.0",
+        "leading zeros are not allowed in decimal integers at line 1 column 5"
+    );
+    bad!(
+        "a = -00.0",
+        "leading zeros are not allowed in decimal integers at line 1 column 6"
+    );
+    bad!(
+        "a = +00.0",
+        "leading zeros are not allowed in decimal integers at line 1 column 6"
+    );
+    bad!(
+        "a = 007",
+        "leading zeros are not allowed in decimal integers at line 1 column 5"
+    );
+
+    // With arbitrary_precision, out of range numbers are only rejected once
+    // deserialized into a type other than basic_toml::Number.
+    let out_of_range = |msg: &str| {
+        if cfg!(feature = "arbitrary_precision") {
+            format!("{} for key `a` at line 1 column 5", msg)
+        } else {
+            format!("{} at line 1 column 5", msg)
+        }
+    };
     bad!(
         "a = 9223372036854775808",
-        "invalid number at line 1 column 5"
+        out_of_range("integer literal exceeds i64::MAX")
     );
     bad!(
         "a = -9223372036854775809",
-        "invalid number at line 1 column 5"
+        out_of_range("integer literal is less than i64::MIN")
+    );
+    bad!(
+        "a = 0x8000000000000000",
+        out_of_range("integer literal exceeds i64::MAX")
+    );
+    bad!(
+        "a = 1e400",
+        out_of_range("float literal is out of range for f64")
     );
 }
 
 #[test]
 fn bad_floats() {
-    bad!("a = 0.", "invalid number at line 1 column 7");
-    bad!("a = 0.e", "invalid number at line 1 column 7");
-    bad!("a = 0.E", "invalid number at line 1 column 7");
-    bad!("a = 0.0E", "invalid number at line 1 column 5");
-    bad!("a = 0.0e", "invalid number at line 1 column 5");
-    bad!("a = 0.0e-", "invalid number at line 1 column 9");
-    bad!("a = 0.0e+", "invalid number at line 1 column 5");
+    bad!(
+        "a = 0.",
+        "expected digits after the decimal point at line 1 column 6"
+    );
+    bad!(
+        "a = 0.e",
+        "expected digits after the decimal point at line 1 column 6"
+    );
+    bad!(
+        "a = 0.E",
+        "expected digits after the decimal point at line 1 column 6"
+    );
+    bad!(
+        "a = 0._1",
+        "underscore must be between digits at line 1 column 7"
+    );
+    bad!(
+        "a = 0.0E",
+        "expected digits after the exponent at line 1 column 8"
+    );
+    bad!(
+        "a = 0.0e",
+        "expected digits after the exponent at line 1 column 8"
+    );
+    bad!(
+        "a = 0.0e-",
+        "expected digits after the exponent at line 1 column 8"
+    );
+    bad!(
+        "a = 0.0e+",
+        "expected digits after the exponent at line 1 column 8"
+    );
+    bad!(
+        "a = 1e",
+        "expected digits after the exponent at line 1 column 6"
+    );
+    bad!(
+        "a = 1E-x",
+        "expected digits after the exponent at line 1 column 6"
+    );
+    bad!(
+        "a = 1e+ 1",
+        "expected digits after the exponent at line 1 column 6"
+    );
+    bad!(
+        "a = 1e-_1",
+        "underscore must be between digits at line 1 column 8"
+    );
+}
+
+#[test]
+fn bad_prefixed_integers() {
+    bad!(
+        "a = 0x-1",
+        "a sign cannot follow the prefix of an integer at line 1 column 7"
+    );
+    bad!(
+        "a = 0o+7",
+        "a sign cannot follow the prefix of an integer at line 1 column 7"
+    );
+    bad!(
+        "a = 0b-_1",
+        "a sign cannot follow the prefix of an integer at line 1 column 7"
+    );
+    bad!("a = 0x", "invalid number at line 1 column 7");
+    bad!("a = 0x 1", "invalid number at line 1 column 7");
 }
 
 #[test]
@@ -463,13 +562,34 @@ fn number_underscores() {
 
 #[test]
 fn bad_underscores() {
-    bad!("foo = 0_", "invalid number at line 1 column 7");
-    bad!("foo = 0__0", "invalid number at line 1 column 7");
+    bad!(
+        "foo = 0_",
+        "underscore must be between digits at line 1 column 8"
+    );
+    bad!(
+        "foo = 1__0",
+        "underscore must be between digits at line 1 column 9"
+    );
+    bad!(
+        "foo = -_1",
+        "underscore must be between digits at line 1 column 8"
+    );
+    bad!(
+        "foo = 1_.5",
+        "underscore must be between digits at line 1 column 8"
+    );
+    bad!(
+        "foo = 1.5_",
+        "underscore must be between digits at line 1 column 10"
+    );
     bad!(
         "foo = __0",
         "invalid TOML value, did you mean to use a quoted string? at line 1 column 7"
     );
-    bad!("foo = 1_0_", "invalid number at line 1 column 7");
+    bad!(
+        "foo = 1_0_",
+        "underscore must be between digits at line 1 column 10"
+    );
 }
 
 #[test]
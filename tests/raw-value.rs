@@ -0,0 +1,132 @@
+use basic_toml::RawValue;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Deserialize)]
+struct Config {
+    name: String,
+    plugins: BTreeMap<String, RawValue>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Greeter {
+    greeting: String,
+    targets: Vec<Target>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Target {
+    name: String,
+}
+
+const TOML: &str = r#"name = "app"
+
+[plugins]
+inline = { greeting = "hi", targets = [] }
+list = [1, 2, 3]
+
+[plugins.greeter]
+greeting = "hello"
+
+[[plugins.greeter.targets]]
+name = "world"
+
+[[plugins.greeter.targets]]
+name = 5
+"#;
+
+#[test]
+fn deserialize_into() {
+    let config: Config = basic_toml::from_str(TOML).unwrap();
+    assert_eq!(config.name, "app");
+    assert_eq!(config.plugins.len(), 3);
+
+    let inline: Greeter = config.plugins["inline"].deserialize_into().unwrap();
+    assert_eq!(inline.greeting, "hi");
+    let list: Vec<u8> = config.plugins["list"].deserialize_into().unwrap();
+    assert_eq!(list, [1, 2, 3]);
+
+    let err = config.plugins["greeter"]
+        .deserialize_into::<Greeter>()
+        .unwrap_err();
+    assert_eq!(err.line_col(), Some((13, 7)));
+    assert_eq!(
+        err.to_string(),
+        "invalid type: integer `5`, expected a string at line 14 column 8",
+    );
+
+    let err = config.plugins["list"]
+        .deserialize_into::<Vec<String>>()
+        .unwrap_err();
+    assert_eq!(err.line_col(), Some((4, 8)));
+
+    let err = config.plugins["inline"]
+        .deserialize_into::<u8>()
+        .unwrap_err();
+    assert_eq!(err.line_col(), Some((3, 9)));
+}
+
+#[test]
+fn serialize() {
+    let config: Config = basic_toml::from_str(TOML).unwrap();
+    let toml = basic_toml::to_string(&config.plugins["greeter"]).unwrap();
+    let expected = r#"greeting = "hello"
+
+[[targets]]
+name = "world"
+
+[[targets]]
+name = 5
+"#;
+    assert_eq!(toml, expected);
+
+    let json = serde_json::to_string(&config.plugins["inline"]).unwrap();
+    assert_eq!(json, r#"{"greeting":"hi","targets":[]}"#);
+}
+
+#[test]
+fn front_matter() {
+    #[derive(Deserialize)]
+    struct Page {
+        extra: RawValue,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Extra {
+        #[allow(dead_code)]
+        width: u32,
+    }
+
+    let markdown = "+++\n[extra]\nwidth = \"wide\"\n+++\n";
+    let page: Page = basic_toml::from_front_matter(markdown).unwrap().unwrap();
+    let err = page.extra.deserialize_into::<Extra>().unwrap_err();
+    assert_eq!(err.line_col(), Some((2, 8)));
+}
+
+#[test]
+fn other_formats() {
+    let err = serde_json::from_str::<RawValue>("1").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: newtype struct, expected a TOML value",
+    );
+}
+
+#[test]
+fn env_override() {
+    std::env::set_var("RAWTEST__PLUGINS__GREETER__GREETING", "5");
+    let config: Config = basic_toml::from_str_with_env(TOML, "RAWTEST", "__").unwrap();
+    let err = config.plugins["greeter"]
+        .deserialize_into::<Greeter>()
+        .unwrap_err();
+    assert_eq!(err.line_col(), None);
+    assert_eq!(
+        err.to_string(),
+        "invalid type: integer `5`, expected a string in environment variable `RAWTEST__PLUGINS__GREETER__GREETING`",
+    );
+
+    let err = config.plugins["list"]
+        .deserialize_into::<Vec<String>>()
+        .unwrap_err();
+    assert_eq!(err.line_col(), Some((4, 8)));
+}
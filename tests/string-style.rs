@@ -0,0 +1,91 @@
+use basic_toml::style::{Basic, Literal, MultilineBasic, MultilineLiteral};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+fn roundtrip(value: &str) -> String {
+    let mut map = BTreeMap::new();
+    map.insert("s", value);
+    let toml = basic_toml::to_string(&map).unwrap();
+    let back: BTreeMap<String, String> = basic_toml::from_str(&toml).unwrap();
+    assert_eq!(back["s"], value);
+    toml
+}
+
+#[test]
+fn automatic() {
+    assert_eq!(roundtrip("plain"), "s = \"plain\"\n");
+    assert_eq!(roundtrip(r"C:\Users\me"), "s = 'C:\\Users\\me'\n");
+    assert_eq!(roundtrip(r"it's C:\"), "s = \"it's C:\\\\\"\n");
+    assert_eq!(roundtrip("one\ntwo"), "s = \"\"\"\none\ntwo\"\"\"\n");
+    assert_eq!(roundtrip("\\d+\n\\w+\n"), "s = '''\n\\d+\n\\w+\n'''\n",);
+    assert_eq!(
+        roundtrip("say \"hi\"\n\"\"\""),
+        "s = \"\"\"\nsay \"hi\"\n\\\"\\\"\\\"\"\"\"\n",
+    );
+
+    for s in [
+        "",
+        "\n",
+        "\n\n",
+        "\"",
+        "\"\"",
+        "'",
+        "'''",
+        "a\\\n",
+        "\\\n'",
+        "x\r\ny",
+        "tab\there\n\u{7f}",
+        "\\\u{1}",
+        "'''\\\n",
+    ] {
+        roundtrip(s);
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Forced {
+    basic: Basic<String>,
+    literal: Literal<String>,
+    multiline_basic: MultilineBasic<String>,
+    multiline_literal: Option<MultilineLiteral<String>>,
+    number: Literal<i32>,
+}
+
+#[test]
+fn forced() {
+    let forced = Forced {
+        basic: Basic(r"C:\".to_owned()),
+        literal: Literal("plain".to_owned()),
+        multiline_basic: MultilineBasic("single line".to_owned()),
+        multiline_literal: Some(MultilineLiteral("a\\b".to_owned())),
+        number: Literal(1),
+    };
+    let toml = basic_toml::to_string(&forced).unwrap();
+    assert_eq!(
+        toml,
+        "\
+basic = \"C:\\\\\"
+literal = 'plain'
+multiline_basic = \"\"\"
+single line\"\"\"
+multiline_literal = '''
+a\\b'''
+number = 1
+",
+    );
+    assert_eq!(basic_toml::from_str::<Forced>(&toml).unwrap(), forced);
+
+    let err = basic_toml::to_string(&Literal("it's")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "string cannot be written as a literal string: \"it's\"",
+    );
+
+    let mut map = BTreeMap::new();
+    map.insert("s", MultilineLiteral("ends with '"));
+    let err = basic_toml::to_string(&map).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "string cannot be written as a multi-line literal string: \"ends with '\"",
+    );
+}
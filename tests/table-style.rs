@@ -0,0 +1,174 @@
+use basic_toml::style::{Dotted, Hex, Inline, Literal, Section};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Dependency {
+    version: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    features: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Point {
+    x: i64,
+    y: i64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Layout {
+    name: String,
+    origin: Inline<Point>,
+    points: Inline<Vec<Point>>,
+    settings: Dotted<BTreeMap<String, BTreeMap<String, Option<i64>>>>,
+    dependencies: BTreeMap<String, Inline<Dependency>>,
+    package: Section<Point>,
+}
+
+fn dependency(version: &str, features: &[&str]) -> Inline<Dependency> {
+    Inline(Dependency {
+        version: version.to_owned(),
+        features: features.iter().map(|&f| f.to_owned()).collect(),
+    })
+}
+
+#[test]
+fn layout() {
+    let mut display = BTreeMap::new();
+    display.insert("width".to_owned(), Some(80));
+    display.insert("height".to_owned(), None);
+    let mut settings = BTreeMap::new();
+    settings.insert("display".to_owned(), display);
+    settings.insert("empty".to_owned(), BTreeMap::new());
+
+    let mut dependencies = BTreeMap::new();
+    dependencies.insert("serde".to_owned(), dependency("1", &["derive"]));
+    dependencies.insert("semver".to_owned(), dependency("1", &[]));
+
+    let layout = Layout {
+        name: "layout".to_owned(),
+        origin: Inline(Point { x: 0, y: 0 }),
+        points: Inline(vec![Point { x: 1, y: 2 }, Point { x: 3, y: 4 }]),
+        settings: Dotted(settings),
+        dependencies,
+        package: Section(Point { x: 5, y: 6 }),
+    };
+
+    let expected = r#"name = "layout"
+origin = { x = 0, y = 0 }
+points = [{ x = 1, y = 2 }, { x = 3, y = 4 }]
+settings.display.width = 80
+settings.empty = {}
+
+[dependencies]
+semver = { version = "1" }
+serde = { version = "1", features = ["derive"] }
+
+[package]
+x = 5
+y = 6
+"#;
+    let toml = basic_toml::to_string(&layout).unwrap();
+    assert_eq!(toml, expected);
+
+    let mut layout = layout;
+    layout
+        .settings
+        .0
+        .get_mut("display")
+        .unwrap()
+        .remove("height");
+    assert_eq!(basic_toml::from_str::<Layout>(&toml).unwrap(), layout);
+}
+
+#[test]
+fn dotted_values() {
+    #[derive(Serialize)]
+    struct Inner {
+        path: Literal<&'static str>,
+        mask: Hex<u8>,
+        list: Vec<Point>,
+        point: Inline<Point>,
+    }
+
+    #[derive(Serialize)]
+    struct Outer {
+        inner: Dotted<Inner>,
+    }
+
+    let outer = Outer {
+        inner: Dotted(Inner {
+            path: Literal(r"C:\"),
+            mask: Hex(0xFF),
+            list: vec![Point { x: 1, y: 2 }],
+            point: Inline(Point { x: 3, y: 4 }),
+        }),
+    };
+    let expected = "\
+inner.path = 'C:\\'
+inner.mask = 0xFF
+inner.list = [{ x = 1, y = 2 }]
+inner.point = { x = 3, y = 4 }
+";
+    assert_eq!(basic_toml::to_string(&outer).unwrap(), expected);
+}
+
+#[test]
+fn dotted_under_header() {
+    #[derive(Serialize)]
+    struct Outer {
+        table: Table,
+    }
+
+    #[derive(Serialize)]
+    struct Table {
+        value: i64,
+        dotted: Dotted<Point>,
+    }
+
+    let outer = Outer {
+        table: Table {
+            value: 1,
+            dotted: Dotted(Point { x: 2, y: 3 }),
+        },
+    };
+    let expected = "\
+[table]
+value = 1
+dotted.x = 2
+dotted.y = 3
+";
+    assert_eq!(basic_toml::to_string(&outer).unwrap(), expected);
+}
+
+#[test]
+fn section_not_allowed() {
+    #[derive(Serialize)]
+    struct InInline {
+        table: Inline<Wrapper>,
+    }
+
+    #[derive(Serialize)]
+    struct InDotted {
+        table: Dotted<Wrapper>,
+    }
+
+    #[derive(Serialize)]
+    struct Wrapper {
+        point: Section<Point>,
+    }
+
+    let wrapper = || Wrapper {
+        point: Section(Point { x: 0, y: 0 }),
+    };
+    let err = basic_toml::to_string(&InInline {
+        table: Inline(wrapper()),
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "table cannot be written as a section here");
+    let err = basic_toml::to_string(&InDotted {
+        table: Dotted(wrapper()),
+    })
+    .unwrap_err();
+    assert_eq!(err.to_string(), "table cannot be written as a section here");
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+enum Point {
+    Cartesian(f64, f64),
+    Polar(f64, f64),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Shape {
+    origin: Point,
+    points: Vec<Point>,
+}
+
+fn shape() -> Shape {
+    Shape {
+        origin: Point::Cartesian(1.0, 2.0),
+        points: vec![Point::Polar(1.0, 0.5), Point::Cartesian(0.0, 0.0)],
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let expected = "\
+origin = { Cartesian = [1.0, 2.0] }
+points = [{ Polar = [1.0, 0.5] }, { Cartesian = [0.0, 0.0] }]
+";
+    let toml = basic_toml::to_string(&shape()).unwrap();
+    assert_eq!(toml, expected);
+    assert_eq!(basic_toml::from_str::<Shape>(&toml).unwrap(), shape());
+
+    let canonical = basic_toml::to_string_canonical(&shape()).unwrap();
+    assert_eq!(basic_toml::from_str::<Shape>(&canonical).unwrap(), shape());
+}
+
+#[test]
+fn top_level() {
+    let point = Point::Polar(2.0, 0.0);
+    let toml = basic_toml::to_string(&point).unwrap();
+    assert_eq!(toml, "Polar = [2.0, 0.0]\n");
+    assert_eq!(basic_toml::from_str::<Point>(&toml).unwrap(), point);
+    assert_eq!(
+        basic_toml::to_value_string(&point).unwrap(),
+        "{ Polar = [2.0, 0.0] }",
+    );
+}
+
+#[test]
+fn section() {
+    let toml = "points = []\n\n[origin]\nCartesian = [3.0, 4.0]\n";
+    let shape: Shape = basic_toml::from_str(toml).unwrap();
+    assert_eq!(shape.origin, Point::Cartesian(3.0, 4.0));
+}
+
+#[test]
+fn numeric_keys() {
+    let toml = "points = []\norigin = { Cartesian = { 0 = 1.0, 1 = 2.0 } }\n";
+    let shape: Shape = basic_toml::from_str(toml).unwrap();
+    assert_eq!(shape.origin, Point::Cartesian(1.0, 2.0));
+}
+
+#[test]
+fn wrong_length() {
+    let toml = "points = []\norigin = { Cartesian = [1.0] }\n";
+    let err = basic_toml::from_str::<Shape>(toml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected tuple with length 2 for key `origin` at line 2 column 24",
+    );
+
+    let toml = "points = []\norigin = { Cartesian = 1.0 }\n";
+    let err = basic_toml::from_str::<Shape>(toml).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected array or table, found float for key `origin` at line 2 column 24",
+    );
+}
@@ -0,0 +1,105 @@
+#![allow(clippy::zero_sized_map_values)]
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Enabled;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Config {
+    enabled: Enabled,
+    unit: (),
+    list: Vec<()>,
+    features: BTreeMap<String, ()>,
+}
+
+#[test]
+fn roundtrip() {
+    let mut features = BTreeMap::new();
+    features.insert("a".to_owned(), ());
+    features.insert("b".to_owned(), ());
+    let config = Config {
+        enabled: Enabled,
+        unit: (),
+        list: vec![(), ()],
+        features,
+    };
+
+    let expected = "\
+enabled = {}
+unit = {}
+list = [{}, {}]
+
+[features]
+a = {}
+b = {}
+";
+    let toml = basic_toml::to_string(&config).unwrap();
+    assert_eq!(toml, expected);
+    assert_eq!(basic_toml::from_str::<Config>(&toml).unwrap(), config);
+
+    let canonical = "\
+[enabled]
+
+[features.a]
+
+[features.b]
+
+[[list]]
+
+[[list]]
+
+[unit]
+";
+    let toml = basic_toml::to_string_canonical(&config).unwrap();
+    assert_eq!(toml, canonical);
+    assert_eq!(basic_toml::from_str::<Config>(&toml).unwrap(), config);
+}
+
+#[test]
+fn sections() {
+    let toml = "\
+list = [{}]
+
+[enabled]
+[unit]
+[features.a]
+[features.b]
+";
+    let config: Config = basic_toml::from_str(toml).unwrap();
+    assert_eq!(config.features.len(), 2);
+}
+
+#[test]
+fn top_level() {
+    assert_eq!(basic_toml::to_string(&()).unwrap(), "");
+    assert_eq!(basic_toml::to_string(&Enabled).unwrap(), "");
+    basic_toml::from_str::<()>("").unwrap();
+    basic_toml::from_str::<Enabled>("").unwrap();
+}
+
+#[test]
+fn not_empty() {
+    #[derive(Debug, Deserialize)]
+    struct Wrapper {
+        #[allow(dead_code)]
+        unit: (),
+    }
+
+    let err = basic_toml::from_str::<Wrapper>("unit = { a = 1 }").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: map, expected unit for key `unit` at line 1 column 8",
+    );
+    let err = basic_toml::from_str::<Wrapper>("[unit]\na = 1").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: map, expected unit for key `unit` at line 1 column 1",
+    );
+    let err = basic_toml::from_str::<Enabled>("a = 1").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: map, expected unit struct Enabled at line 1 column 1",
+    );
+}
@@ -349,6 +349,12 @@ test!(
     include_str!("valid/dotted-keys.json")
 );
 
+test!(
+    dotted_keys_subtables,
+    include_str!("valid/dotted-keys-subtables.toml"),
+    include_str!("valid/dotted-keys-subtables.json")
+);
+
 test!(
     quote_surrounded_value,
     include_str!("valid/quote-surrounded-value.toml"),
@@ -0,0 +1,68 @@
+#![allow(clippy::float_cmp)]
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Dependency {
+    version: String,
+    features: Vec<String>,
+}
+
+#[test]
+fn roundtrip() {
+    let dependency = Dependency {
+        version: "1".to_owned(),
+        features: vec!["derive".to_owned()],
+    };
+    let toml = basic_toml::to_value_string(&dependency).unwrap();
+    assert_eq!(toml, r#"{ version = "1", features = ["derive"] }"#);
+    assert_eq!(
+        basic_toml::from_value_str::<Dependency>(&toml).unwrap(),
+        dependency,
+    );
+}
+
+#[test]
+fn scalars() {
+    assert_eq!(basic_toml::to_value_string(&5).unwrap(), "5");
+    assert_eq!(basic_toml::to_value_string(&1.5).unwrap(), "1.5");
+    assert_eq!(basic_toml::to_value_string("a\"b").unwrap(), r#""a\"b""#);
+    assert_eq!(
+        basic_toml::to_value_string(&vec![1, 2, 3]).unwrap(),
+        "[1, 2, 3]"
+    );
+
+    assert_eq!(basic_toml::from_value_str::<i32>(" 5 ").unwrap(), 5);
+    assert_eq!(basic_toml::from_value_str::<f64>("1.5").unwrap(), 1.5);
+    assert_eq!(basic_toml::from_value_str::<String>("'a'").unwrap(), "a");
+    assert_eq!(
+        basic_toml::from_value_str::<Vec<i32>>("[\n  1,\n  2,\n]").unwrap(),
+        [1, 2],
+    );
+}
+
+#[test]
+fn errors() {
+    let err = basic_toml::from_value_str::<i32>("1 2").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "expected end of value, found an identifier at line 1 column 3",
+    );
+    let err = basic_toml::from_value_str::<i32>("").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "unexpected eof encountered at line 1 column 1",
+    );
+    let err = basic_toml::from_value_str::<Vec<i32>>("[1, \"a\"]").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"a\", expected i32 at line 1 column 5",
+    );
+    let err = basic_toml::from_value_str::<Dependency>("{ version = \"1\" }").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "missing field `features` at line 1 column 1",
+    );
+    let err = basic_toml::to_value_string(&None::<i32>).unwrap_err();
+    assert_eq!(err.to_string(), "unsupported None value");
+}
@@ -0,0 +1,79 @@
+use basic_toml::{Options, Version};
+use serde_json::{json, Value};
+
+fn v1_0(toml: &str) -> Result<Value, String> {
+    basic_toml::from_str(toml).map_err(|e| e.to_string())
+}
+
+fn v1_1(toml: &str) -> Result<Value, String> {
+    let options = Options::new().version(Version::V1_1);
+    options.from_str(toml).map_err(|e| e.to_string())
+}
+
+#[test]
+fn inline_table_newlines() {
+    let toml = "\
+point = {
+    x = 1, # comment
+    y = { z = 2 }
+
+}
+";
+    assert_eq!(
+        v1_1(toml).unwrap(),
+        json!({ "point": { "x": 1, "y": { "z": 2 } } }),
+    );
+    assert_eq!(
+        v1_0(toml).unwrap_err(),
+        "expected a table key, found a newline at line 1 column 10",
+    );
+    assert_eq!(v1_1("a = {\n}").unwrap(), json!({ "a": {} }));
+    assert_eq!(
+        v1_1("a = {\nb\n= 1}").unwrap_err(),
+        "expected an equals, found a newline at line 2 column 2",
+    );
+}
+
+#[test]
+fn inline_table_trailing_comma() {
+    assert_eq!(v1_1("a = { b = 1, }").unwrap(), json!({ "a": { "b": 1 } }));
+    assert_eq!(
+        v1_0("a = { b = 1, }").unwrap_err(),
+        "expected a table key, found a right brace at line 1 column 14",
+    );
+    assert_eq!(
+        v1_1("a = { , }").unwrap_err(),
+        "expected a table key, found a comma at line 1 column 7",
+    );
+    assert_eq!(
+        v1_1("a = { b = 1,, }").unwrap_err(),
+        "expected a table key, found a comma at line 1 column 13",
+    );
+}
+
+#[test]
+fn escape_character() {
+    assert_eq!(v1_1(r#"a = "\e[0m""#).unwrap(), json!({ "a": "\u{1b}[0m" }));
+    assert_eq!(
+        v1_0(r#"a = "\e[0m""#).unwrap_err(),
+        "invalid escape character in string: `e` at line 1 column 7",
+    );
+}
+
+#[test]
+fn hex_escape() {
+    assert_eq!(
+        v1_1(r#"a = "\x41\x00\xff\xFF""#).unwrap(),
+        json!({ "a": "A\u{0}\u{ff}\u{ff}" }),
+    );
+    assert_eq!(v1_1(r#"a = """\x0a""""#).unwrap(), json!({ "a": "\n" }));
+    assert_eq!(
+        v1_1(r#"a = "\x4""#).unwrap_err(),
+        "invalid hex escape character in string: `\\\"` at line 1 column 9",
+    );
+    assert_eq!(
+        v1_0(r#"a = "\x41""#).unwrap_err(),
+        "invalid escape character in string: `x` at line 1 column 7",
+    );
+    assert_eq!(v1_1(r"a = '\x41'").unwrap(), json!({ "a": "\\x41" }));
+}